@@ -1,5 +1,10 @@
 //use std::{error::Error, io::stdin};
-use engine::{Engine, GPUCamera, GPUSprite, Game};
+use engine::{
+    ActionMap, ActionMapError, AnimationClip, Animator, AtlasBuilder, AxisDirection, Binding,
+    BitmapFont, Engine, GPUCamera, GPUSprite, Game, GamepadAxis, GamepadButton, Handle,
+    NumberDisplay, NumberFormat, PlayMode, Scene, SpriteError, SpriteGroupId, TextLabel,
+    TextLayout, TextureAtlas, TileMap,
+};
 use kira::{
     manager::{
         backend::DefaultBackend, // changed to default backend
@@ -8,464 +13,1094 @@ use kira::{
     },
     sound::static_sound::{StaticSoundData, StaticSoundSettings},
 };
+use winit::event::{MouseButton, VirtualKeyCode};
 struct TestGame {
     //move some sctucts into here {
     camera: GPUCamera,
     audio_manager: AudioManager,
     sound_data: StaticSoundData,
+    groups: Option<Groups>,
+    hud: Option<Hud>,
+    atlas: Option<Handle<TextureAtlas>>,
+    player_animation: Option<PlayerAnimation>,
+    // Solid rectangles from the level, and where the powerup appears
+    blockers: Vec<[f32; 4]>,
+    // Every tile layer in the level, however many tiles each has
+    platforms: Vec<SpriteGroupId>,
+    powerup_spawn: [f32; 4],
+    is_jumping: bool,
+    leftis_jumping: bool,
+    velocity_y: f32,
+    leftvelocity_y: f32,
+    gravity: f32,
+    score: usize,
+    single_player: bool,
+    sub_score: usize,
+    audio_cues: bool,
+    left_keyboard: bool,
+    high_contrast: bool,
+    already_removed_multi: bool,
+    options_shown: bool,
+    p1_speed: f32,
+    p2_speed: f32, // This is the movement speed that will be affected when using powerups
+    // Seconds since the powerups last wore off
+    powerup_time: f32,
+}
+
+// How long a powerup's speed boost lasts
+const POWERUP_SECONDS: f32 = 7.0;
+
+// Multiplied into a player sprite while it's being hit
+const HIT_TINT: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+
+// font.png, and the glyphs in it row by row
+fn font_png(engine: &mut Engine) -> BitmapFont {
+    let texture = engine
+        .texture("scene2d/src/font.png")
+        .expect("Couldn't load font");
+    BitmapFont::grid(
+        texture,
+        [8, 8],
+        concat!(
+            "ABCDEFGHI",
+            "JKLMNOPQR",
+            "STUVWXYZ ",
+            "abcdefghi",
+            "jklmnopqr",
+            "stuvwxyz0",
+            "123456789",
+            "~`!@#$%^&",
+            "*()-_+=[]",
+            "{}|\\:;\"'.",
+            ",<>/?",
+        ),
+    )
 }
 
-struct Resources{
-    bgm: StaticSoundData,
-    jump: StaticSoundData,
+// Sprite group layers, back to front. screens.json puts its groups on these too.
+const LAYER_HUD: i32 = 1;
+const LAYER_TITLE: i32 = 2;
+const LAYER_MENU: i32 = 3;
+
+// The score counter and the font it's drawn with
+struct Hud {
+    font: BitmapFont,
+    score: NumberDisplay,
+}
+
+// The right player's animations and what's playing on it
+struct PlayerAnimation {
+    animator: Animator,
+    idle: Handle<AnimationClip>,
+    walk: Handle<AnimationClip>,
+    jump: Handle<AnimationClip>,
 }
+
+// Handles for every sprite group TestGame creates in `init`
+#[derive(Clone, Copy)]
+struct Groups {
+    background: SpriteGroupId,
+    left_player: SpriteGroupId,
+    right_player: SpriteGroupId,
+    powerups: SpriteGroupId,
+    title_background: SpriteGroupId,
+    title: SpriteGroupId,
+    options_label: SpriteGroupId,
+    play_label: SpriteGroupId,
+    cursor: SpriteGroupId,
+    options_background: SpriteGroupId,
+    option_checks: SpriteGroupId,
+}
+
+// Players can put their own controls here, in ActionMap's format
+const CONTROLS_PATH: &str = "controls.json";
+
+// One player's controls. The right player (player one) gets the arrow keys and the left player
+// WASD, or the other way round with the "Left Keyboard" option, until the left player picks up a
+// pad (see share_devices).
+fn controls(
+    left: VirtualKeyCode,
+    right: VirtualKeyCode,
+    up: VirtualKeyCode,
+    down: VirtualKeyCode,
+) -> ActionMap {
+    let mut actions = ActionMap::new();
+    actions
+        .bind("left", left)
+        .bind("right", right)
+        .bind("up", up)
+        .bind("down", down)
+        .bind("confirm", VirtualKeyCode::Return)
+        .bind("back", VirtualKeyCode::Escape);
+    actions.bind_axis("move_x", left, right);
+
+    // Their own gamepad works too
+    let stick = |direction| Binding::GamepadAxis(GamepadAxis::LeftStickX, direction);
+    actions
+        .bind("left", GamepadButton::DPadLeft)
+        .bind("left", stick(AxisDirection::Negative))
+        .bind("right", GamepadButton::DPadRight)
+        .bind("right", stick(AxisDirection::Positive))
+        .bind("up", GamepadButton::South)
+        .bind("down", GamepadButton::DPadDown)
+        .bind("confirm", GamepadButton::Start)
+        .bind("back", GamepadButton::East);
+    actions.bind_axis(
+        "move_x",
+        stick(AxisDirection::Negative),
+        stick(AxisDirection::Positive),
+    );
+    actions
+}
+fn arrow_keys() -> ActionMap {
+    use VirtualKeyCode::*;
+    controls(Left, Right, Up, Down)
+}
+fn wasd() -> ActionMap {
+    use VirtualKeyCode::*;
+    controls(A, D, W, S)
+}
+
 #[async_trait::async_trait]
 impl Game for TestGame {
     async fn init(&mut self, engine: &mut Engine) {
-        //Creating our background image texture, by calling load texture
-        let (img, _) = engine
-            .load_texture("scene2d/src/background.jpg", None)
+        // Pick up edits to textures and the shader without restarting
+        #[cfg(debug_assertions)]
+        engine.set_hot_reload(true);
+
+        let actions = match ActionMap::load(CONTROLS_PATH) {
+            Ok(actions) => actions,
+            // No controls file is fine, everyone starts out on the arrow keys
+            Err(ActionMapError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                arrow_keys()
+            }
+            Err(err) => {
+                log::warn!("Ignoring {}: {}", CONTROLS_PATH, err);
+                arrow_keys()
+            }
+        };
+        engine.input.set_actions(actions);
+        engine.input.set_player_actions(1, wasd());
+
+        //Creating our background image texture, through the cache so it hot reloads
+        let img = engine
+            .texture("scene2d/src/background.jpg")
             .expect("Couldn't load background");
 
+        // Everything small goes in one atlas: the kiiiii.png sprites plus the pillar tile
+        let mut sheet = AtlasBuilder::new();
+        sheet
+            .add_manifest("scene2d/src/kiiiii.json")
+            .expect("Couldn't load sprite sheet");
+        sheet
+            .add_file_region("pillar", "scene2d/src/background.jpg", [300, 800, 30, 30])
+            .expect("Couldn't cut out pillar");
+        let atlas = engine.atlas(sheet).expect("Couldn't build atlas");
+        let region = |name: &str| atlas.region(name).expect("missing atlas region");
+
         //Then we are adding this and behind the scenes it shoudl be creating a bind group and etc to display it.
-        engine.sprites.add_sprite_group(
+        let background = engine.sprites.add_sprite_group(
             &engine.gpu,
             &img,
-            vec![GPUSprite {
-                screen_region: [0.0, 0.0, 1024.0, 768.0],
-                sheet_region: [0.0, 0.0, 1.0, 1.0],
-            }],
+            vec![GPUSprite::new(
+                [0.0, 0.0, 1024.0, 768.0],
+                [0.0, 0.0, 1.0, 1.0],
+            )],
             self.camera,
         );
 
-        //Same thing but for our sprite
-        let (tex_king, _) = engine
-            .load_texture("scene2d/src/kiiiii.png", None)
-            .expect("Couldn't load king img");
-
-        //This sprite-group we would want to add obstacles and etc
-        engine.sprites.add_sprite_group(
-            &engine.gpu,
-            &tex_king,
-            vec![
-                GPUSprite {
-                    screen_region: [400.0, 200.0, 64.0, 64.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [400.0 + 64.0, 200.0, 64.0, 64.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [400.0 + 128.0, 200.0, 64.0, 64.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [700.0 + 192.0, 200.0, 64.0, 64.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-            ],
-            self.camera,
-        );
+        // The platforms, blockers and spawn points come from the Tiled level
+        let mut level = TileMap::load("scene2d/src/level1.tmj").expect("Couldn't load level");
+        // 16 pixel tiles, drawn 64 pixels wide
+        level.scale = 4.0;
+        self.platforms = level
+            .add_tile_layers(engine, self.camera)
+            .expect("Couldn't load level tiles");
+        self.blockers = level.colliders();
+        let spawn = |name: &str| {
+            let [x, y] = level.spawn_point(name).expect("missing spawn point");
+            [x, y, 64.0, 64.0]
+        };
+        self.powerup_spawn = spawn("powerup");
 
         //This sprite group adds the left Player
-        engine.sprites.add_sprite_group(
+        let left_player = engine.sprites.add_sprite_group(
             &engine.gpu,
-            &tex_king,
+            atlas.texture(),
             vec![
                 //It's the 2 different sprites for king.png at 2 different locations
-                GPUSprite {
-                    screen_region: [32.0, 85.0, 64.0, 64.0],
-                    sheet_region: [32.0 / 64.0, 48.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0],
-                },
+                GPUSprite::new(spawn("left_player"), region("rival")),
             ],
             self.camera,
         );
 
         //This sprite group adds the right player
-        engine.sprites.add_sprite_group(
+        let right_player = engine.sprites.add_sprite_group(
             &engine.gpu,
-            &tex_king,
+            atlas.texture(),
             vec![
                 //It's the 2 different sprites for king.png at 2 different locations
-                GPUSprite {
-                    screen_region: [750.0, 85.0, 64.0, 64.0],
-                    sheet_region: [32.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0],
-                },
+                GPUSprite::new(spawn("right_player"), region("player_idle")),
             ],
             self.camera,
         );
         let _ = self.audio_manager.play(self.sound_data.clone());
 
         //72x88 (9 by 11 - so 8 each)
-        let (font, _) = engine
-            .load_texture("scene2d/src/font.png", None)
-            .expect("Couldn't load background");
+        let mut font = font_png(engine);
 
         //music engine
 
         //Then we are adding this and behind the scenes it shoudl be creating a bind group and etc to display it.
         //Overlay
-        engine.sprites.add_sprite_group(
+        let hud = TextLabel::new(
+            &mut engine.sprites,
             &engine.gpu,
-            &font,
-            vec![
-                GPUSprite {
-                    screen_region: [2.0, 728.0, 32.0, 32.0],
-                    sheet_region: [0.0, 16.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0, 728.0, 32.0, 32.0],
-                    sheet_region: [16.0 / 72.0, 0.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 2.0, 728.0, 32.0, 32.0],
-                    sheet_region: [40.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 3.0, 728.0, 32.0, 32.0],
-                    sheet_region: [64.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 4.0, 728.0, 32.0, 32.0],
-                    sheet_region: [32.0 / 72.0, 0.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 4.0 + 16.0, 726.0, 32.0, 32.0],
-                    sheet_region: [32.0 / 72.0, 72.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 5.0 + 8.0, 728.0, 32.0, 32.0],
-                    sheet_region: [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 6.0 + 8.0, 728.0, 32.0, 32.0],
-                    sheet_region: [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    screen_region: [2.0 + 32.0 * 7.0 + 8.0, 728.0, 32.0, 32.0],
-                    sheet_region: [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-            ],
+            &mut font,
+            TextLayout {
+                position: [2.0, 728.0],
+                glyph_size: [32.0, 32.0],
+                spacing: 0.0,
+                wrap_width: None,
+            },
+            "SCORE:",
             self.camera,
-        );
-
-        //Powerups - 5
-        engine.sprites.add_sprite_group(
+        )
+        .group();
+        let score_display = NumberDisplay::new(
+            &mut engine.sprites,
             &engine.gpu,
-            &tex_king,
-            vec![GPUSprite {
-                screen_region: [560.0, 90.0, 64.0, 64.0],
-                sheet_region: [32.0 / 64.0, 0.0, 16.0 / 64.0, 16.0 / 64.0],
-            }],
+            &mut font,
+            TextLayout {
+                position: [2.0 + 32.0 * 6.0, 728.0],
+                glyph_size: [32.0, 32.0],
+                spacing: 0.0,
+                wrap_width: None,
+            },
+            NumberFormat {
+                min_digits: 3,
+                leading_zeros: true,
+                ..NumberFormat::default()
+            },
             self.camera,
         );
 
-        let (img, _) = engine
-            .load_texture("scene2d/src/titleScreenBackground.jpg", None)
-            .expect("Couldn't load background");
-
-        //Title Screen Background
-        engine.sprites.add_sprite_group(
+        //Powerups - 5
+        let powerups = engine.sprites.add_sprite_group(
             &engine.gpu,
-            &img,
-            vec![GPUSprite {
-                screen_region: [0.0, 0.0, 1024.0, 768.0],
-                sheet_region: [0.0, 0.0, 1.0, 1.0],
-            }],
+            atlas.texture(),
+            vec![GPUSprite::new(self.powerup_spawn, region("powerup"))],
             self.camera,
         );
 
-        let (img, _) = engine
-            .load_texture("scene2d/src/font.png", None)
-            .expect("Couldn't load background");
+        // The title and options screen backgrounds are laid out in screens.json
+        let screens = Scene::load("scene2d/src/screens.json")
+            .and_then(|scene| scene.spawn(engine))
+            .unwrap_or_else(|err| panic!("Couldn't load screens: {}", err));
+        let screen = |name: &str| screens.group(name).expect("missing screen group");
+        let title_background = screen("title_background");
+        let options_background = screen("options_background");
 
-        let starting_x = 200.0;
-        let y_val = 600.0;
         //GAMENAME
-        engine.sprites.add_sprite_group(
+        let title = TextLabel::new(
+            &mut engine.sprites,
             &engine.gpu,
-            &img,
-            vec![
-                GPUSprite {
-                    //P
-                    screen_region: [starting_x, y_val, 64.0, 64.0],
-                    sheet_region: [48.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //L
-                    screen_region: [starting_x + 66.0, y_val, 64.0, 64.0],
-                    sheet_region: [16.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //A
-                    screen_region: [starting_x + (66.0 * 2.0), y_val, 64.0, 64.0],
-                    sheet_region: [0.0 / 72.0, 0.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //T
-                    screen_region: [starting_x + (66.0 * 3.0), y_val, 64.0, 64.0],
-                    sheet_region: [8.0 / 72.0, 16.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //F
-                    screen_region: [starting_x + (66.0 * 4.0), y_val, 64.0, 64.0],
-                    sheet_region: [40.0 / 72.0, 0.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //O
-                    screen_region: [starting_x + (66.0 * 5.0), y_val, 64.0, 64.0],
-                    sheet_region: [40.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //R
-                    screen_region: [starting_x + (66.0 * 6.0), y_val, 64.0, 64.0],
-                    sheet_region: [64.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //M
-                    screen_region: [starting_x + (66.0 * 7.0), y_val, 64.0, 64.0],
-                    sheet_region: [24.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //E
-                    screen_region: [starting_x + (66.0 * 8.0), y_val, 64.0, 64.0],
-                    sheet_region: [32.0 / 72.0, 0.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //R
-                    screen_region: [starting_x + (66.0 * 9.0), y_val, 64.0, 64.0],
-                    sheet_region: [64.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-            ],
+            &mut font,
+            TextLayout {
+                position: [200.0, 600.0],
+                glyph_size: [64.0, 64.0],
+                spacing: 2.0,
+                wrap_width: None,
+            },
+            "PLATFORMER",
             self.camera,
-        );
-
-        let (img, _) = engine
-            .load_texture("scene2d/src/font.png", None)
-            .expect("Couldn't load background");
+        )
+        .group();
 
-        let starting_x = 234.0;
-        let y_val = 500.0;
+        let menu_item = |y| TextLayout {
+            position: [234.0, y],
+            glyph_size: [32.0, 32.0],
+            spacing: 2.0,
+            wrap_width: None,
+        };
         //OPTIONS
-        engine.sprites.add_sprite_group(
+        let options_label = TextLabel::new(
+            &mut engine.sprites,
             &engine.gpu,
-            &img,
-            vec![
-                GPUSprite {
-                    //O
-                    screen_region: [starting_x, y_val, 32.0, 32.0],
-                    sheet_region: [40.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //P
-                    screen_region: [starting_x + 34.0, y_val, 32.0, 32.0],
-                    sheet_region: [48.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //T
-                    screen_region: [starting_x + (34.0 * 2.0), y_val, 32.0, 32.0],
-                    sheet_region: [8.0 / 72.0, 16.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //I
-                    screen_region: [starting_x + (34.0 * 3.0), y_val, 32.0, 32.0],
-                    sheet_region: [64.0 / 72.0, 0.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //O
-                    screen_region: [starting_x + (34.0 * 4.0), y_val, 32.0, 32.0],
-                    sheet_region: [40.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //N
-                    screen_region: [starting_x + (34.0 * 5.0), y_val, 32.0, 32.0],
-                    sheet_region: [32.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //S
-                    screen_region: [starting_x + (34.0 * 6.0), y_val, 32.0, 32.0],
-                    sheet_region: [0.0 / 72.0, 16.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-            ],
+            &mut font,
+            menu_item(500.0),
+            "OPTIONS",
             self.camera,
-        );
+        )
+        .group();
 
-        let (img, _) = engine
-            .load_texture("scene2d/src/font.png", None)
-            .expect("Couldn't load background");
-
-        let y_val = 400.0;
         //PLAY
-        engine.sprites.add_sprite_group(
+        let play_label = TextLabel::new(
+            &mut engine.sprites,
             &engine.gpu,
-            &img,
-            vec![
-                GPUSprite {
-                    //P
-                    screen_region: [starting_x, y_val, 32.0, 32.0],
-                    sheet_region: [48.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //L
-                    screen_region: [starting_x + 34.0, y_val, 32.0, 32.0],
-                    sheet_region: [16.0 / 72.0, 8.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //A
-                    screen_region: [starting_x + (34.0 * 2.0), y_val, 32.0, 32.0],
-                    sheet_region: [0.0 / 72.0, 0.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-                GPUSprite {
-                    //Y
-                    screen_region: [starting_x + (34.0 * 3.0), y_val, 32.0, 32.0],
-                    sheet_region: [48.0 / 72.0, 16.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-                },
-            ],
+            &mut font,
+            menu_item(400.0),
+            "PLAY",
             self.camera,
-        );
-
-        let (img, _) = engine
-            .load_texture("scene2d/src/font.png", None)
-            .expect("Couldn't load background");
+        )
+        .group();
 
         //Select thing
-        engine.sprites.add_sprite_group(
+        let cursor = TextLabel::new(
+            &mut engine.sprites,
             &engine.gpu,
-            &img,
-            vec![GPUSprite {
-                //>
-                screen_region: [200.0, 500.0, 32.0, 32.0],
-                sheet_region: [16.0 / 72.0, 80.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-            }],
+            &mut font,
+            TextLayout {
+                position: [200.0, 500.0],
+                glyph_size: [32.0, 32.0],
+                spacing: 0.0,
+                wrap_width: None,
+            },
+            ">",
             self.camera,
-        );
-
-        let (tex_king, _) = engine
-            .load_texture("scene2d/src/background.jpg", None)
-            .expect("Couldn't load king img");
+        )
+        .group();
 
         //This sprite-group we would want to add obstacles and etc
         engine.sprites.add_sprite_group(
             &engine.gpu,
-            &tex_king,
+            atlas.texture(),
             vec![
-                GPUSprite {
-                    screen_region: [300.0, 0.0, 0.0, 0.0], //[300.0, 0.0, 64.0, 85.0],
-                    sheet_region: [300.0 / 1600.0, 800.0 / 1200.0, 30.0 / 1600.0, 30.0 / 1200.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [800.0, 0.0, 0.0, 0.0], //[800.0, 0.0, 64.0, 85.0],
-                    sheet_region: [300.0 / 1600.0, 800.0 / 1200.0, 30.0 / 1600.0, 30.0 / 1200.0], //[0.0, 0.5, 0.5, 0.5]
-                },
+                GPUSprite::new(
+                    [300.0, 0.0, 0.0, 0.0], //[300.0, 0.0, 64.0, 85.0],
+                    region("pillar"),
+                ),
+                GPUSprite::new(
+                    [800.0, 0.0, 0.0, 0.0], //[800.0, 0.0, 64.0, 85.0],
+                    region("pillar"),
+                ),
             ],
             self.camera,
         );
 
-        let (tex_king, _) = engine
-            .load_texture("scene2d/src/kiiiii.png", None)
-            .expect("Couldn't load king img");
+        // OPTIONS MENU
+        // Same font.png the labels use, so this doesn't load it again
+        let img = engine
+            .texture("scene2d/src/font.png")
+            .expect("Couldn't load font");
+        let check = font.sheet_region('>').expect("font.png has a >");
 
-        //This sprite-group we would want to add obstacles and etc
-        engine.sprites.add_sprite_group(
+        //more select things
+        let option_checks = engine.sprites.add_sprite_group(
             &engine.gpu,
-            &tex_king,
+            &img,
             vec![
-                GPUSprite {
-                    screen_region: [400.0, 200.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [400.0 + 64.0, 200.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [400.0 + 128.0, 200.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
+                GPUSprite::new(
+                    //> High Contrast
+                    [40.0, 425.0, 32.0, 32.0],
+                    check,
+                ),
+                GPUSprite::new(
+                    //> Audio Cues
+                    [40.0, 375.0, 32.0, 32.0],
+                    check,
+                ),
+                GPUSprite::new(
+                    //> Left Keyboard
+                    [40.0, 325.0, 32.0, 32.0],
+                    check,
+                ),
             ],
             self.camera,
         );
 
-        let (tex_king, _) = engine
-            .load_texture("scene2d/src/kiiiii.png", None)
-            .expect("Couldn't load king img");
+        // The options menu stays hidden until it's opened, and so do the checks for options
+        // that are off
+        engine
+            .sprites
+            .set_group_visible(option_checks, false)
+            .expect("group was just added");
+        for i in 0..3 {
+            engine
+                .sprites
+                .set_sprite_visible(option_checks, i, false)
+                .expect("option_checks has three sprites");
+        }
 
-        //This sprite-group we would want to add obstacles and etc
-        engine.sprites.add_sprite_group(
-            &engine.gpu,
-            &tex_king,
-            vec![
-                GPUSprite {
-                    screen_region: [150.0, 72.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [150.0, 130.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [850.0, 72.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-                GPUSprite {
-                    screen_region: [850.0, 130.0, 0.0, 0.0],
-                    sheet_region: [0.0, 0.0, 16.0 / 64.0, 16.0 / 64.0], //[0.0, 0.5, 0.5, 0.5]
-                },
-            ],
-            self.camera,
-        );
+        // Everything else stays on the world layer (0)
+        let layers = [
+            (hud, LAYER_HUD),
+            (score_display.group(), LAYER_HUD),
+            (title, LAYER_TITLE),
+            (options_label, LAYER_TITLE),
+            (play_label, LAYER_TITLE),
+            (cursor, LAYER_TITLE),
+            (option_checks, LAYER_MENU),
+        ];
+        for (group, layer) in layers {
+            engine
+                .sprites
+                .set_layer(group, layer)
+                .expect("group was just added");
+        }
 
-        // OPTIONS MENU
-        let (img, _) = engine
-        .load_texture("scene2d/src/optionBackground.png", None)
-        .expect("Couldn't load background");
+        self.hud = Some(Hud {
+            font,
+            score: score_display,
+        });
+        self.groups = Some(Groups {
+            background,
+            left_player,
+            right_player,
+            powerups,
+            title_background,
+            title,
+            options_label,
+            play_label,
+            cursor,
+            options_background,
+            option_checks,
+        });
+        let idle = Handle::new(AnimationClip::still(region("player_idle")));
+        let walk = Handle::new(AnimationClip::uniform(
+            [region("player_walk"), region("player_idle")],
+            0.15,
+            PlayMode::Loop,
+        ));
+        let jump = Handle::new(AnimationClip::still(region("player_walk")));
+        self.player_animation = Some(PlayerAnimation {
+            animator: Animator::new(right_player, 0, idle.clone()),
+            idle,
+            walk,
+            jump,
+        });
+        self.atlas = Some(atlas);
+    }
 
-        engine.sprites.add_sprite_group(
-            &engine.gpu,
-            &img,
-            vec![GPUSprite {
-                screen_region: [0.0, 0.0, 0.0, 0.0],
-                sheet_region: [0.0, 0.0, 1.0, 1.0],
-            }],
-            self.camera,
-        );
+    fn update(&mut self, engine: &mut Engine, dt: f32) {
+        let Some(groups) = self.groups else {
+            return;
+        };
+        self.share_devices(engine);
+        self.step(engine, groups, dt)
+            .expect("TestGame's sprite groups are never removed");
+    }
+}
 
-        let (img, _) = engine
-            .load_texture("scene2d/src/font.png", None)
-            .expect("Couldn't load background");
+impl TestGame {
+    // The named sprite's sheet_region in the atlas built in init
+    fn region(&self, name: &str) -> [f32; 4] {
+        self.atlas
+            .as_ref()
+            .and_then(|atlas| atlas.region(name))
+            .expect("missing atlas region")
+    }
 
-        //more select things
-        engine.sprites.add_sprite_group(
-            &engine.gpu,
-            &img,
-            vec![
-            GPUSprite {
-                //> High Contrast
-                screen_region: [40.0, 425.0, 0.0, 0.0],
-                sheet_region: [16.0 / 72.0, 80.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-            },
-            GPUSprite {
-                //> Audio Cues
-                screen_region: [40.0, 375.0, 0.0, 0.0],
-                sheet_region: [16.0 / 72.0, 80.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-            },
-            GPUSprite {
-                //> Left Keyboard
-                screen_region: [40.0, 325.0, 0.0, 0.0],
-                sheet_region: [16.0 / 72.0, 80.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-            }],
-            self.camera,
-        );
+    // Whether a sprite spanning sprite_x..sprite_xw is above or below one of the level's
+    // blockers (with a little leeway at the edges)
+    fn over_blocker(&self, sprite_x: f32, sprite_xw: f32) -> bool {
+        self.blockers
+            .iter()
+            .any(|b| sprite_xw - 5.0 > b[0] && sprite_x + 5.0 < b[0] + b[2])
+    }
+    // Whether a sprite whose bottom is at sprite_y would be inside a blocker
+    fn in_blocker(&self, sprite_x: f32, sprite_xw: f32, sprite_y: f32) -> bool {
+        self.blockers.iter().any(|b| {
+            sprite_xw - 5.0 > b[0] && sprite_x + 5.0 < b[0] + b[2] && sprite_y < b[1] + b[3]
+        })
+    }
+
+    // Whether a player at `region` would overlap one of the platforms
+    fn hits_platform(&self, engine: &Engine, region: [f32; 4]) -> Result<bool, SpriteError> {
+        let [x, y, w, h] = region;
+        for &group in &self.platforms {
+            let hit = engine.sprites.get_sprites(group)?.iter().any(|platform| {
+                let [px, py, pw, ph] = platform.screen_region;
+                x + w - 5.0 > px && x + 5.0 < px + pw && y + h > py && y < py + ph
+            });
+            if hit {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
+    // Whether a jumping player whose top is at `top` has bumped into the underside of a platform
+    fn under_platform(&self, engine: &Engine, top: f32) -> Result<bool, SpriteError> {
+        for &group in &self.platforms {
+            let hit = engine.sprites.get_sprites(group)?.iter().any(|platform| {
+                let bottom = platform.screen_region[1];
+                top > bottom - 1.0 && top < bottom + 1.0
+            });
+            if hit {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+    // Slides every platform sideways as the single player level scrolls. With `wrap`, platforms
+    // that leave on the left come back on the right.
+    fn scroll_platforms(
+        &self,
+        engine: &mut Engine,
+        dx: f32,
+        wrap: bool,
+    ) -> Result<(), SpriteError> {
+        for &group in &self.platforms {
+            for platform in engine.sprites.get_all_sprites_mut(group)? {
+                let [x, y, w, h] = platform.screen_region;
+                platform.screen_region = [x + dx, y, w, h];
+                if wrap && x + dx < -64.0 {
+                    platform.screen_region = [1084.0, y, w, h];
+                }
+            }
+        }
+        Ok(())
+    }
+    // Moves a player `dx` pixels sideways in two player mode, unless a platform, a blocker or
+    // the edge of the screen is in the way
+    fn walk(&self, engine: &mut Engine, player: SpriteGroupId, dx: f32) -> Result<(), SpriteError> {
+        let old_region = engine.sprites.get_sprites(player)?[0].screen_region;
+        let new_region = [
+            old_region[0] + dx,
+            old_region[1],
+            old_region[2],
+            old_region[3],
+        ];
+        let [x, y, w, _] = new_region;
+        let blocked = self.hits_platform(engine, new_region)? || self.in_blocker(x, x + w, y);
+        if !blocked && x > -5.0 && x + w < 1030.0 {
+            engine.sprites.update_position(new_region, player)?;
+        }
+        Ok(())
+    }
+
+    // In two player mode the first pad goes to player two, who then leaves the keyboard to
+    // player one, and a second pad goes to player one. Without a pad player two is back on the
+    // keyboard. In single player mode the pad is player one's.
+    fn share_devices(&self, engine: &mut Engine) {
+        let (gets_pad, other) = if self.single_player { (0, 1) } else { (1, 0) };
+        if engine.input.player_gamepad(gets_pad).is_none() {
+            if let Some(pad) = engine.input.player_gamepad(other) {
+                engine.input.assign_gamepad(gets_pad, pad);
+            }
+        }
+        let on_keyboard = self.single_player || engine.input.player_gamepad(1).is_none();
+        engine.input.set_player_keyboard(1, on_keyboard);
+    }
+
+    // Sounds are decoded the first time they're played and cached after that. Only the samples
+    // are cached, so every play gets its own settings.
+    fn play_sound(&mut self, engine: &mut Engine, path: &str, settings: StaticSoundSettings) {
+        let sound = engine
+            .assets
+            .load(path, |path| {
+                StaticSoundData::from_file(path, StaticSoundSettings::default())
+            })
+            .expect("Couldn't load sound");
+        let _ = self.audio_manager.play(sound.with_settings(settings));
+    }
+
+    fn show_score(&mut self, engine: &mut Engine) -> Result<(), SpriteError> {
+        match &mut self.hud {
+            Some(hud) => hud.score.set_value(
+                &mut engine.sprites,
+                &engine.gpu,
+                &mut hud.font,
+                self.score as i64,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    fn step(&mut self, engine: &mut Engine, g: Groups, dt: f32) -> Result<(), SpriteError> {
+        if self.powerup_time > POWERUP_SECONDS {
+            //Slight Problem: If you get a powerup close to 6 seconds, the powerup will be removed immediatly
+
+            self.powerup_time = 0.0;
+            self.p1_speed = 3.0;
+            self.p2_speed = 3.0;
+            //Refresh the sprites after they run out
+            if !self.single_player {
+                let powerup = GPUSprite::new(self.powerup_spawn, self.region("powerup"));
+                respawn_powerup(engine, g.powerups, powerup)?;
+            }
+        }
+
+        // set high contrast
+        if engine.input.is_key_pressed(VirtualKeyCode::Key1) && self.options_shown {
+            self.high_contrast = !self.high_contrast;
+            engine
+                .sprites
+                .set_sprite_visible(g.option_checks, 0, self.high_contrast)?;
+        }
+
+        // set audio cues
+        if engine.input.is_key_pressed(VirtualKeyCode::Key2) && self.options_shown {
+            self.audio_cues = !self.audio_cues;
+            engine
+                .sprites
+                .set_sprite_visible(g.option_checks, 1, self.audio_cues)?;
+        }
+
+        // set left keyboard
+        if engine.input.is_key_pressed(VirtualKeyCode::Key3) && self.options_shown {
+            self.left_keyboard = !self.left_keyboard;
+            engine
+                .sprites
+                .set_sprite_visible(g.option_checks, 2, self.left_keyboard)?;
+            let (right_player, left_player) = if self.left_keyboard {
+                (wasd(), arrow_keys())
+            } else {
+                (arrow_keys(), wasd())
+            };
+            engine.input.set_player_actions(0, right_player);
+            engine.input.set_player_actions(1, left_player);
+        }
+
+        // The title menu works with the mouse too: point at an item to move the cursor to it,
+        // and click to pick it
+        let mut clicked = false;
+        if !self.options_shown {
+            let mouse = engine.input.mouse_world_pos(&self.camera);
+            let pointed_at = if engine.sprites.sprite_at(g.play_label, mouse)?.is_some() {
+                Some(400.0)
+            } else if engine.sprites.sprite_at(g.options_label, mouse)?.is_some() {
+                Some(500.0)
+            } else {
+                None
+            };
+            if let Some(y) = pointed_at {
+                let old_position = engine.sprites.get_sprites(g.cursor)?[0].screen_region;
+                if old_position[1] != y {
+                    engine
+                        .sprites
+                        .update_position([200.0, y, old_position[2], old_position[3]], g.cursor)?;
+                }
+                clicked = engine.input.is_mouse_pressed(MouseButton::Left);
+            }
+        }
+
+        if engine.input.is_action_down("confirm") || clicked {
+            // PLAY
+            if engine.sprites.get_sprites(g.cursor)?[0].screen_region[1] == 400.0 {
+                self.single_player = true;
+            }
+
+            if !self.single_player {
+                if !self.options_shown {
+                    engine
+                        .sprites
+                        .set_group_visible(g.options_background, true)?;
+                    engine.sprites.set_group_visible(g.option_checks, true)?;
+                    self.options_shown = true;
+                }
+            } else {
+                //platformer
+                for group in [
+                    g.title_background,
+                    g.title,
+                    g.options_label,
+                    g.play_label,
+                    g.cursor,
+                ] {
+                    engine.sprites.set_group_visible(group, false)?;
+                }
+
+                if !self.already_removed_multi {
+                    // hide multiplayer stuff behind title screen
+                    engine.sprites.set_group_visible(g.left_player, false)?;
+                    let old_region = engine.sprites.get_sprites(g.background)?[0].sheet_region;
+                    engine.sprites.update_sprite(
+                        [old_region[0], old_region[1], 0.75, old_region[3]],
+                        g.background,
+                    )?;
+                    let new_region = [150.0, 85.0, 64.0, 64.0];
+                    engine.sprites.update_position(new_region, g.right_player)?;
+                    // No powerups in single player
+                    while !engine.sprites.get_sprites(g.powerups)?.is_empty() {
+                        engine.sprites.swap_remove_sprite(g.powerups, 0)?;
+                    }
+                    self.already_removed_multi = true;
+                }
+            }
+        }
+
+        if engine.input.is_action_down("back") && self.options_shown {
+            engine
+                .sprites
+                .set_group_visible(g.options_background, false)?;
+            engine.sprites.set_group_visible(g.option_checks, false)?;
+            self.options_shown = false;
+        }
+
+        if engine.input.is_action_down("down") {
+            let old_position = engine.sprites.get_sprites(g.cursor)?[0].screen_region;
+            engine
+                .sprites
+                .update_position([200.0, 400.0, old_position[2], old_position[3]], g.cursor)?;
+        }
+        if engine.input.is_action_down("up") {
+            let old_position = engine.sprites.get_sprites(g.cursor)?[0].screen_region;
+            engine
+                .sprites
+                .update_position([200.0, 500.0, old_position[2], old_position[3]], g.cursor)?;
+        }
+
+        if engine.input.is_action_down("up") && !self.is_jumping {
+            //engine.sprites.update_sprite_score([0.0, 0.54545456, 0.11111111, 0.09090909], g.hud, 0);
+            self.is_jumping = true;
+            self.velocity_y = 20.0; // This will be the upward force or the initial jump velocity. Adjust as needed.
+            self.play_sound(
+                engine,
+                "scene2d/src/musiccontent/jump.mp3",
+                StaticSoundSettings::default(),
+            );
+        }
+
+        if self.leftis_jumping {
+            let mut the_move = true;
+            let old_region = engine.sprites.get_sprites(g.left_player)?[0].screen_region;
+            self.leftvelocity_y += self.gravity; // Apply gravity to velocity
+            let new_y = old_region[1] + self.leftvelocity_y;
+
+            let sprite_x = old_region[0];
+            let sprite_xw = old_region[0] + old_region[2];
+            let sprite_y = new_y;
+            let sprite_yh = new_y + old_region[3];
+
+            if self.hits_platform(engine, [sprite_x, sprite_y, old_region[2], old_region[3]])? {
+                the_move = false;
+            }
+
+            if !self.single_player && self.in_blocker(sprite_x, sprite_xw, sprite_y) {
+                the_move = false;
+            }
+
+            if the_move {
+                let new_region = [old_region[0], new_y, old_region[2], old_region[3]];
+                engine.sprites.update_position(new_region, g.left_player)?;
+            }
+
+            // Check if the sprite has landed.
+            // Assuming 85.0 is ground level.
+            if new_y <= 85.0 || self.under_platform(engine, sprite_yh)? {
+                self.leftis_jumping = false;
+                self.leftvelocity_y = 0.0;
+            }
+        }
+
+        if self.is_jumping {
+            let mut the_move = true;
+            let old_region = engine.sprites.get_sprites(g.right_player)?[0].screen_region;
+            self.velocity_y += self.gravity; // Apply gravity to velocity
+            let new_y = old_region[1] + self.velocity_y;
+
+            let sprite_x = old_region[0];
+            let sprite_xw = old_region[0] + old_region[2];
+            let sprite_y = new_y;
+            let sprite_yh = new_y + old_region[3];
+
+            if self.hits_platform(engine, [sprite_x, sprite_y, old_region[2], old_region[3]])? {
+                the_move = false;
+            }
+            if !self.single_player && self.in_blocker(sprite_x, sprite_xw, sprite_y) {
+                the_move = false;
+            }
+            if the_move {
+                let new_region = [old_region[0], new_y, old_region[2], old_region[3]];
+                engine.sprites.update_position(new_region, g.right_player)?;
+            }
+
+            // Check if the sprite has landed.a
+            // Assuming 85.0 is ground level.
+            if new_y <= 85.0 || self.under_platform(engine, sprite_yh)? {
+                self.is_jumping = false;
+                self.velocity_y = 0.0;
+                self.play_sound(
+                    engine,
+                    "scene2d/src/musiccontent/landed.mp3",
+                    StaticSoundSettings::default().volume(2.5),
+                );
+            }
+        }
+
+        if engine.input.is_action_down("right") {
+            if self.single_player {
+                self.sub_score += 1;
+                if self.sub_score == 5 {
+                    self.score += 1;
+                    self.show_score(engine)?;
+
+                    self.sub_score = 0;
+                }
+            }
+
+            let player = engine.sprites.get_sprite_mut(g.right_player, 0)?;
+            player.set_flag(GPUSprite::FLIP_X, false);
+            if self.single_player {
+                let new_region = engine.sprites.get_sprites(g.right_player)?[0].screen_region;
+                let the_move = !self.hits_platform(engine, new_region)?;
+
+                if the_move {
+                    let old_sheet_pos = engine.sprites.get_sprites(g.background)?[0].sheet_region;
+                    engine.sprites.update_sprite(
+                        [
+                            old_sheet_pos[0] + (self.p2_speed / 3000.0),
+                            old_sheet_pos[1],
+                            0.75,
+                            old_sheet_pos[3],
+                        ],
+                        g.background,
+                    )?;
+                    if old_sheet_pos[0] + self.p2_speed / 3000.0 > 1.0 {
+                        engine.sprites.update_sprite(
+                            [-1.0, old_sheet_pos[1], 0.75, old_sheet_pos[3]],
+                            g.background,
+                        )?;
+                    }
+                    self.scroll_platforms(engine, -self.p2_speed, true)?;
+                } else {
+                    self.is_jumping = false;
+                }
+            } else {
+                self.walk(engine, g.right_player, self.p2_speed)?;
+            }
+        }
+        if engine.input.is_action_down("left") {
+            // Same walk cycle, mirrored
+            let player = engine.sprites.get_sprite_mut(g.right_player, 0)?;
+            player.set_flag(GPUSprite::FLIP_X, true);
+            if self.single_player {
+                let new_region = engine.sprites.get_sprites(g.right_player)?[0].screen_region;
+                let the_move = !self.hits_platform(engine, new_region)?;
+
+                if the_move {
+                    let old_sheet_pos = engine.sprites.get_sprites(g.background)?[0].sheet_region;
+                    engine.sprites.update_sprite(
+                        [
+                            old_sheet_pos[0] - (self.p2_speed / 3000.0),
+                            old_sheet_pos[1],
+                            0.75,
+                            old_sheet_pos[3],
+                        ],
+                        g.background,
+                    )?;
+                    if old_sheet_pos[0] + self.p2_speed / 3000.0 < -1.0 {
+                        engine.sprites.update_sprite(
+                            [1.0, old_sheet_pos[1], 0.75, old_sheet_pos[3]],
+                            g.background,
+                        )?;
+                    }
+                    self.scroll_platforms(engine, self.p2_speed, false)?;
+                } else {
+                    self.leftis_jumping = false;
+                }
+
+                let old_sheet_pos = engine.sprites.get_sprites(g.background)?[0].sheet_region;
+                engine.sprites.update_sprite(
+                    [
+                        old_sheet_pos[0] - (self.p2_speed / 3000.0),
+                        old_sheet_pos[1],
+                        0.75,
+                        old_sheet_pos[3],
+                    ],
+                    g.background,
+                )?;
+
+                self.scroll_platforms(engine, self.p2_speed, false)?;
+            } else {
+                self.walk(engine, g.right_player, -self.p2_speed)?;
+            }
+        }
+
+        // The left player only plays in two player mode, with player two's controls
+        if !self.single_player {
+            let controls = engine.input.player(1);
+            let jump = controls.is_action_down("up");
+            let dx = controls.action_axis("move_x") * self.p1_speed;
+            if jump && !self.leftis_jumping {
+                self.leftis_jumping = true;
+                self.leftvelocity_y = 20.0;
+                self.play_sound(
+                    engine,
+                    "scene2d/src/musiccontent/jump.mp3",
+                    StaticSoundSettings::default(),
+                );
+            }
+            if dx != 0.0 {
+                let player = engine.sprites.get_sprite_mut(g.left_player, 0)?;
+                player.set_flag(GPUSprite::FLIP_X, dx < 0.0);
+                self.walk(engine, g.left_player, dx)?;
+            }
+        }
+
+        if engine.sprites.get_sprites(g.right_player)?[0].screen_region[1] < 85.0 {
+            let old_region = engine.sprites.get_sprites(g.right_player)?[0].screen_region;
+            let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
+            engine.sprites.update_position(new_region, g.right_player)?;
+        }
+
+        if engine.sprites.get_sprites(g.left_player)?[0].screen_region[1] < 85.0 {
+            let old_region = engine.sprites.get_sprites(g.left_player)?[0].screen_region;
+            let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
+            engine.sprites.update_position(new_region, g.left_player)?;
+        }
+        if engine.sprites.get_sprites(g.right_player)?[0].screen_region[1] < 260.0
+            && !self.is_jumping
+        {
+            let old_region = engine.sprites.get_sprites(g.right_player)?[0].screen_region;
+
+            if self.single_player {
+                let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
+                engine.sprites.update_position(new_region, g.right_player)?;
+            } else {
+                let sprite_x = old_region[0];
+                let sprite_xw = old_region[0] + old_region[2];
+                if !self.over_blocker(sprite_x, sprite_xw) {
+                    let old_region = engine.sprites.get_sprites(g.right_player)?[0].screen_region;
+
+                    let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
+                    engine.sprites.update_position(new_region, g.right_player)?;
+                }
+            }
+        }
+        if engine.sprites.get_sprites(g.left_player)?[0].screen_region[1] < 260.0
+            && !self.leftis_jumping
+        {
+            let old_region = engine.sprites.get_sprites(g.left_player)?[0].screen_region;
+
+            if self.single_player {
+                let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
+                engine.sprites.update_position(new_region, g.left_player)?;
+            } else {
+                let sprite_x = old_region[0];
+                let sprite_xw = old_region[0] + old_region[2];
+                if !self.over_blocker(sprite_x, sprite_xw) {
+                    let old_region = engine.sprites.get_sprites(g.left_player)?[0].screen_region;
+
+                    let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
+                    engine.sprites.update_position(new_region, g.left_player)?;
+                }
+            }
+        }
+
+        //Checking if the p1 sprite, collides with group 5, which are only powerups (Speed Up Powerups)
+        if check_collisions(engine, g.left_player, g.powerups)? {
+            //If so we update their speed (How many pixels they travel across the screen)
+            self.p1_speed = 7.0;
+        }
+
+        //Check Player2
+        if check_collisions(engine, g.right_player, g.powerups)? {
+            self.p2_speed = 7.0
+        }
 
-    fn update(&mut self, engine: &mut Engine) {
-        //put here
         engine.sprites.set_camera_all(&engine.gpu, self.camera);
-        engine
-            .sprites
-            .refresh_sprites(&engine.gpu, 0, 0..(engine.sprites.get_sprites(0).len()));
+        // The left player is only hidden in single player, so it can't be tagged either
+        let touching =
+            !self.single_player && player_collision(engine, g.left_player, g.right_player)?;
+        // Flash the right player red while they're being tagged
+        let tint = if touching { HIT_TINT } else { GPUSprite::WHITE };
+        if engine.sprites.get_sprites(g.right_player)?[0].color != tint {
+            engine.sprites.get_sprite_mut(g.right_player, 0)?.color = tint;
+        }
+        if touching {
+            if self.score > 100 {
+                //Queue Winning Screen
+                println!("Uhh some player one Shrug");
+                engine.quit();
+            }
+            self.score += 5;
+            self.show_score(engine)?;
+        }
+        self.animate_player(engine, dt)?;
+        self.powerup_time += dt;
+        Ok(())
+    }
+
+    // Picks the right player's clip for what they're doing and moves it along
+    fn animate_player(&mut self, engine: &mut Engine, dt: f32) -> Result<(), SpriteError> {
+        let Some(anim) = &mut self.player_animation else {
+            return Ok(());
+        };
+        let walking = engine.input.action_axis("move_x") != 0.0;
+        let clip = if self.is_jumping {
+            &anim.jump
+        } else if walking {
+            &anim.walk
+        } else {
+            &anim.idle
+        };
+        anim.animator.play(clip);
+        anim.animator.update(&mut engine.sprites, dt)
+    }
+}
+
+fn check_collisions(
+    engine: &mut Engine,
+    player: SpriteGroupId,
+    which: SpriteGroupId,
+) -> Result<bool, SpriteError> {
+    //The which for this function is the sprite group we want to go through
+    let player_sprite = engine.sprites.get_sprites(player)?[0]; //Getting the player sprite
+                                                                //Gettting and storing all the screen positions
+    let topleft = player_sprite.screen_region[0];
+    //let topright = player_sprite.screen_region[0] + player_sprite.screen_region[2];
+    let bottomleft = player_sprite.screen_region[1];
+    let bottomright = player_sprite.screen_region[1] + player_sprite.screen_region[3];
+
+    //Getting all sprites within said sprite group
+    let all_sprites = engine.sprites.get_sprites(which)?;
+    for (index, sprite) in all_sprites.iter().enumerate() {
+        //Looping through all of the sprites and determinign if their is any overlap within their positions.
+        let tl = sprite.screen_region[0];
+        let tr = sprite.screen_region[0] + sprite.screen_region[2];
+        let bl = sprite.screen_region[1];
+        let br = sprite.screen_region[1] + sprite.screen_region[3];
+
+        if topleft < tl || topleft > tr {
+            continue; // No horizontal overlap
+        }
+
+        if bottomright < bl || bottomleft > br {
+            continue; // No vertical overlap
+        }
+
+        // println!("Collision");
+
+        //Then removing it from the group, order doesn't matter for powerups
+        engine.sprites.swap_remove_sprite(which, index)?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+fn player_collision(
+    engine: &Engine,
+    player1: SpriteGroupId,
+    player2: SpriteGroupId,
+) -> Result<bool, SpriteError> {
+    let player1 = engine.sprites.get_sprites(player1)?[0];
+    let player2 = engine.sprites.get_sprites(player2)?[0];
+
+    //Comparing if they are overlapping in any way
+    //I have an offset of 60 pixels, becasue without the offset, they would collide way before actual;y interacting.  I just guessed and checked
+    if player1.screen_region[0] < player2.screen_region[0] + player2.screen_region[2] - 60.0
+        && player1.screen_region[0] + player1.screen_region[2] - 60.0 > player2.screen_region[0]
+        && player1.screen_region[1] < player2.screen_region[1] + player2.screen_region[3] - 60.0
+        && player1.screen_region[1] + player1.screen_region[3] - 60.0 > player2.screen_region[1]
+    {
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+fn respawn_powerup(
+    engine: &mut Engine,
+    which: SpriteGroupId,
+    powerup: GPUSprite,
+) -> Result<(), SpriteError> {
+    //If the powerup was picked up it's gone from the group, so we put a fresh one back
+    if engine.sprites.get_sprites(which)?.is_empty() {
+        engine.sprites.push_sprite(&engine.gpu, which, powerup)?;
     }
+    Ok(())
 }
 
 fn main() {
@@ -488,6 +1123,29 @@ fn main() {
             camera,
             audio_manager: manager,
             sound_data,
+            groups: None,
+            hud: None,
+            atlas: None,
+            player_animation: None,
+            blockers: Vec::new(),
+            platforms: Vec::new(),
+            powerup_spawn: [0.0; 4],
+            is_jumping: false,
+            leftis_jumping: false,
+            velocity_y: 0.0,
+            leftvelocity_y: 0.0,
+            gravity: -0.8, // Per tick, and there are 60 a second. Negative as it will pull the sprite down.
+            score: 0,
+            single_player: false,
+            sub_score: 0,
+            audio_cues: false,
+            left_keyboard: false,
+            high_contrast: false,
+            already_removed_multi: false,
+            options_shown: false,
+            p1_speed: 3.0,
+            p2_speed: 3.0,
+            powerup_time: 0.0,
         },
     );
 }
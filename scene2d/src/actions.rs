@@ -0,0 +1,153 @@
+use crate::input::Key;
+use crate::{GamepadAxis, GamepadButton};
+use std::collections::BTreeMap;
+use std::path::Path;
+use winit::event::MouseButton;
+
+#[derive(Debug)]
+pub enum ActionMapError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for ActionMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionMapError::Io(err) => write!(f, "couldn't read or write controls: {}", err),
+            ActionMapError::Json(err) => write!(f, "bad controls file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ActionMapError {}
+
+impl From<std::io::Error> for ActionMapError {
+    fn from(err: std::io::Error) -> Self {
+        ActionMapError::Io(err)
+    }
+}
+impl From<serde_json::Error> for ActionMapError {
+    fn from(err: serde_json::Error) -> Self {
+        ActionMapError::Json(err)
+    }
+}
+
+// One physical input an action can be bound to. In a controls file it's {"key": "Space"},
+// {"mouse": "Left"}, {"gamepad_button": "south"} or {"gamepad_axis": ["left_stick_x",
+// "negative"]}.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButton),
+    // One direction of a stick or trigger, which is down once it's pushed halfway
+    GamepadAxis(GamepadAxis, AxisDirection),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisDirection {
+    Negative,
+    Positive,
+}
+
+impl From<Key> for Binding {
+    fn from(key: Key) -> Self {
+        Binding::Key(key)
+    }
+}
+impl From<MouseButton> for Binding {
+    fn from(button: MouseButton) -> Self {
+        Binding::Mouse(button)
+    }
+}
+impl From<GamepadButton> for Binding {
+    fn from(button: GamepadButton) -> Self {
+        Binding::GamepadButton(button)
+    }
+}
+
+// What moves an axis: anything in `negative` pulls it to -1, anything in `positive` to 1
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AxisBinding {
+    #[serde(default)]
+    pub negative: Vec<Binding>,
+    #[serde(default)]
+    pub positive: Vec<Binding>,
+}
+
+// Named actions ("jump") and axes ("move_x") and what they're bound to, so games ask about
+// what the player wants to do rather than which keys are down. Input checks it for
+// is_action_down and friends. Saved as JSON, like
+//     { "actions": { "jump": [{ "key": "Up" }, { "key": "Space" }] },
+//       "axes": { "move_x": { "negative": [{ "key": "Left" }], "positive": [{ "key": "Right" }] } } }
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ActionMap {
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    axes: BTreeMap<String, AxisBinding>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ActionMapError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ActionMapError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    // Adds another way to trigger `action`, keeping the ones it already has
+    pub fn bind(&mut self, action: &str, binding: impl Into<Binding>) -> &mut Self {
+        let binding = binding.into();
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+    pub fn unbind(&mut self, action: &str, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+    // Replaces everything bound to `action`, e.g. after the player picks a new key for it
+    pub fn rebind(&mut self, action: &str, bindings: impl IntoIterator<Item = Binding>) {
+        self.actions
+            .insert(action.to_string(), bindings.into_iter().collect());
+    }
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(String::as_str)
+    }
+
+    pub fn bind_axis(
+        &mut self,
+        axis: &str,
+        negative: impl Into<Binding>,
+        positive: impl Into<Binding>,
+    ) -> &mut Self {
+        let bindings = self.axes.entry(axis.to_string()).or_default();
+        bindings.negative.push(negative.into());
+        bindings.positive.push(positive.into());
+        self
+    }
+    pub fn rebind_axis(&mut self, axis: &str, bindings: AxisBinding) {
+        self.axes.insert(axis.to_string(), bindings);
+    }
+    pub fn axis_bindings(&self, axis: &str) -> Option<&AxisBinding> {
+        self.axes.get(axis)
+    }
+    pub fn axes(&self) -> impl Iterator<Item = &str> {
+        self.axes.keys().map(String::as_str)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{Handle, SpriteError, SpriteGroupId, SpriteRender};
+
+// What happens when a clip reaches its last frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayMode {
+    // Start over from the first frame
+    #[default]
+    Loop,
+    // Play backwards to the first frame, then forwards again
+    PingPong,
+    // Stop on the last frame
+    Once,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+pub struct Frame {
+    pub sheet_region: [f32; 4],
+    // How long the frame stays up, in seconds
+    pub duration: f32,
+}
+
+// A sequence of sheet frames, e.g. a walk cycle. Can be loaded from JSON like
+//     { "mode": "ping_pong", "frames": [{ "sheet_region": [0, 0.25, 0.25, 0.25], "duration": 0.1 }] }
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct AnimationClip {
+    pub frames: Vec<Frame>,
+    #[serde(default)]
+    pub mode: PlayMode,
+}
+
+impl AnimationClip {
+    pub fn new(frames: Vec<Frame>, mode: PlayMode) -> Self {
+        Self { frames, mode }
+    }
+    // Every frame up for the same amount of time
+    pub fn uniform(
+        sheet_regions: impl IntoIterator<Item = [f32; 4]>,
+        frame_duration: f32,
+        mode: PlayMode,
+    ) -> Self {
+        let frames = sheet_regions
+            .into_iter()
+            .map(|sheet_region| Frame {
+                sheet_region,
+                duration: frame_duration,
+            })
+            .collect();
+        Self::new(frames, mode)
+    }
+    // A single frame that never changes, like standing still
+    pub fn still(sheet_region: [f32; 4]) -> Self {
+        Self::uniform([sheet_region], 1.0, PlayMode::Once)
+    }
+
+    // Frame indices for one trip through the clip. Ping-pong doesn't repeat the end frames.
+    fn sequence(&self) -> impl Iterator<Item = usize> {
+        let n = self.frames.len();
+        let back = match self.mode {
+            PlayMode::PingPong => (1..n.saturating_sub(1)).rev(),
+            _ => (0..0).rev(),
+        };
+        (0..n).chain(back)
+    }
+    // How long one trip through the clip takes
+    pub fn cycle_duration(&self) -> f32 {
+        self.sequence().map(|i| self.frames[i].duration).sum()
+    }
+
+    // Which frame is showing `time` seconds after the clip started
+    pub fn frame_at(&self, time: f32) -> usize {
+        let cycle = self.cycle_duration();
+        if cycle <= 0.0 {
+            return 0;
+        }
+        let mut t = match self.mode {
+            PlayMode::Once => time.min(cycle),
+            PlayMode::Loop | PlayMode::PingPong => time.rem_euclid(cycle),
+        };
+        let mut last = 0;
+        for i in self.sequence() {
+            if t < self.frames[i].duration {
+                return i;
+            }
+            t -= self.frames[i].duration;
+            last = i;
+        }
+        // Only reachable at the very end of a one-shot clip (or through rounding)
+        last
+    }
+    // Whether a one-shot clip has played all the way through by `time`
+    pub fn finished_at(&self, time: f32) -> bool {
+        self.mode == PlayMode::Once && time >= self.cycle_duration()
+    }
+}
+
+// Plays clips on one sprite, writing each frame's sheet_region into it as time passes
+pub struct Animator {
+    group: SpriteGroupId,
+    index: usize,
+    clip: Handle<AnimationClip>,
+    time: f32,
+    // Multiplies dt, so 2.0 plays twice as fast
+    pub speed: f32,
+    // The frame last written to the sprite, so it's only touched when the frame changes
+    shown: Option<usize>,
+}
+
+impl Animator {
+    pub fn new(group: SpriteGroupId, index: usize, clip: Handle<AnimationClip>) -> Self {
+        Self {
+            group,
+            index,
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            shown: None,
+        }
+    }
+
+    // Switches to `clip` from its first frame. Does nothing if it's already playing, so this
+    // can be called every frame with whatever clip the sprite should be showing.
+    pub fn play(&mut self, clip: &Handle<AnimationClip>) {
+        if !self.clip.ptr_eq(clip) {
+            self.clip = clip.clone();
+            self.restart();
+        }
+    }
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.shown = None;
+    }
+
+    pub fn clip(&self) -> &Handle<AnimationClip> {
+        &self.clip
+    }
+    pub fn frame(&self) -> usize {
+        self.clip.frame_at(self.time)
+    }
+    pub fn finished(&self) -> bool {
+        self.clip.finished_at(self.time)
+    }
+
+    // Moves the clip `dt` seconds along and updates the sprite if that changed the frame
+    pub fn update(&mut self, sprites: &mut SpriteRender, dt: f32) -> Result<(), SpriteError> {
+        self.time += dt * self.speed;
+        let frame = self.frame();
+        if self.shown == Some(frame) {
+            return Ok(());
+        }
+        if let Some(&Frame { sheet_region, .. }) = self.clip.frames.get(frame) {
+            sprites.get_sprite_mut(self.group, self.index)?.sheet_region = sheet_region;
+        }
+        self.shown = Some(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Durations are powers of two so frame boundaries land exactly
+    fn clip(durations: &[f32], mode: PlayMode) -> AnimationClip {
+        let frames = durations
+            .iter()
+            .map(|&duration| Frame {
+                sheet_region: [0.0; 4],
+                duration,
+            })
+            .collect();
+        AnimationClip::new(frames, mode)
+    }
+
+    fn frames_at(clip: &AnimationClip, times: &[f32]) -> Vec<usize> {
+        times.iter().map(|&t| clip.frame_at(t)).collect()
+    }
+
+    #[test]
+    fn loops_start_over() {
+        let clip = clip(&[0.25; 4], PlayMode::Loop);
+        assert_eq!(clip.cycle_duration(), 1.0);
+        assert_eq!(
+            frames_at(&clip, &[0.0, 0.24, 0.25, 0.75, 0.99, 1.0, 1.25, 10.5]),
+            [0, 0, 1, 3, 3, 0, 1, 2]
+        );
+        assert!(!clip.finished_at(10.0));
+    }
+
+    #[test]
+    fn one_shots_stop_on_the_last_frame() {
+        let clip = clip(&[0.25; 4], PlayMode::Once);
+        assert_eq!(
+            frames_at(&clip, &[0.0, 0.5, 0.99, 1.0, 100.0]),
+            [0, 2, 3, 3, 3]
+        );
+        assert!(!clip.finished_at(0.99));
+        assert!(clip.finished_at(1.0));
+        assert!(clip.finished_at(100.0));
+    }
+
+    #[test]
+    fn ping_pong_turns_around_without_repeating_the_ends() {
+        // 0 1 2 3 2 1, then 0 again
+        let four = clip(&[0.25; 4], PlayMode::PingPong);
+        assert_eq!(four.cycle_duration(), 1.5);
+        assert_eq!(
+            frames_at(&four, &[0.0, 0.5, 0.75, 1.0, 1.25, 1.49, 1.5, 2.25]),
+            [0, 2, 3, 2, 1, 1, 0, 3]
+        );
+        // Two frames just alternate, and one never changes
+        let two = clip(&[0.25; 2], PlayMode::PingPong);
+        assert_eq!(frames_at(&two, &[0.0, 0.25, 0.5, 0.75]), [0, 1, 0, 1]);
+        let one = clip(&[0.25], PlayMode::PingPong);
+        assert_eq!(frames_at(&one, &[0.0, 0.3, 7.0]), [0, 0, 0]);
+    }
+
+    #[test]
+    fn frames_can_last_different_times() {
+        let durations = [0.5, 0.25, 1.0];
+        let looped = clip(&durations, PlayMode::Loop);
+        assert_eq!(
+            frames_at(&looped, &[0.49, 0.5, 0.75, 1.74, 1.75, 2.25]),
+            [0, 1, 2, 2, 0, 1]
+        );
+        // Going back, the middle frame still only gets its own 0.25
+        let ping_pong = clip(&durations, PlayMode::PingPong);
+        assert_eq!(ping_pong.cycle_duration(), 2.0);
+        assert_eq!(
+            frames_at(&ping_pong, &[1.74, 1.75, 1.99, 2.0]),
+            [2, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn empty_clips_stay_on_frame_zero() {
+        for mode in [PlayMode::Loop, PlayMode::PingPong, PlayMode::Once] {
+            assert_eq!(clip(&[], mode).frame_at(1.0), 0);
+            assert_eq!(clip(&[0.0, 0.0], mode).frame_at(1.0), 0);
+        }
+    }
+}
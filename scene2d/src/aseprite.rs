@@ -0,0 +1,488 @@
+use crate::{AnimationClip, AtlasError, Frame, PlayMode, TextureAtlas, WGPU};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum AsepriteError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Atlas(AtlasError),
+    // A frame tag's range runs past the last frame
+    BadTag(String),
+    // A slice has the same name as a frame
+    DuplicateName(String),
+}
+
+impl std::fmt::Display for AsepriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsepriteError::Io(err) => write!(f, "couldn't read Aseprite export: {}", err),
+            AsepriteError::Json(err) => write!(f, "bad Aseprite export: {}", err),
+            AsepriteError::Atlas(err) => write!(f, "{}", err),
+            AsepriteError::BadTag(name) => {
+                write!(f, "frame tag {:?} refers to frames that aren't there", name)
+            }
+            AsepriteError::DuplicateName(name) => {
+                write!(f, "slice {:?} has the same name as a frame", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsepriteError {}
+
+impl From<std::io::Error> for AsepriteError {
+    fn from(err: std::io::Error) -> Self {
+        AsepriteError::Io(err)
+    }
+}
+impl From<serde_json::Error> for AsepriteError {
+    fn from(err: serde_json::Error) -> Self {
+        AsepriteError::Json(err)
+    }
+}
+impl From<AtlasError> for AsepriteError {
+    fn from(err: AtlasError) -> Self {
+        AsepriteError::Atlas(err)
+    }
+}
+impl From<image::ImageError> for AsepriteError {
+    fn from(err: image::ImageError) -> Self {
+        AsepriteError::Atlas(AtlasError::Image(err))
+    }
+}
+
+// Just the parts of Aseprite's JSON export we use. Both the "Hash" and "Array" frame layouts
+// are accepted.
+#[derive(serde::Deserialize)]
+struct Export {
+    frames: ExportFrames,
+    meta: Meta,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum ExportFrames {
+    Array(Vec<ArrayFrame>),
+    Hash(FrameMap),
+}
+
+#[derive(serde::Deserialize)]
+struct ArrayFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: ExportFrame,
+}
+
+#[derive(serde::Deserialize)]
+struct ExportFrame {
+    frame: Rect,
+    // Milliseconds
+    duration: u32,
+}
+
+// A JSON object of frames, kept in file order. That's the animation order, and sorting the
+// names would put "walk 10" before "walk 2".
+struct FrameMap(Vec<(String, ExportFrame)>);
+
+impl<'de> serde::Deserialize<'de> for FrameMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = FrameMap;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a map of frame names to frames")
+            }
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<FrameMap, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    frames.push(entry);
+                }
+                Ok(FrameMap(frames))
+            }
+        }
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+#[derive(Clone, Copy, serde::Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl Rect {
+    fn pixels(self) -> [u32; 4] {
+        [self.x, self.y, self.w, self.h]
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Meta {
+    image: PathBuf,
+    size: Size,
+    #[serde(default)]
+    frame_tags: Vec<Tag>,
+    #[serde(default)]
+    slices: Vec<Slice>,
+}
+
+#[derive(serde::Deserialize)]
+struct Tag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: Direction,
+    // How many times to play it, as a string. Missing means forever.
+    #[serde(default)]
+    repeat: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+#[derive(serde::Deserialize)]
+struct Slice {
+    name: String,
+    keys: Vec<SliceKey>,
+}
+
+#[derive(serde::Deserialize)]
+struct SliceKey {
+    bounds: Rect,
+}
+
+// An Aseprite sprite sheet export: the PNG plus the JSON Aseprite writes next to it
+// (File > Export Sprite Sheet, with "JSON Data" ticked). Frames and slices become atlas
+// regions and frame tags become animation clips.
+pub struct AsepriteSheet {
+    image: PathBuf,
+    size: [u32; 2],
+    // Name, pixel rectangle and duration in seconds, in animation order
+    frames: Vec<(String, [u32; 4], f32)>,
+    // Slices only keep their first key's bounds
+    slices: Vec<(String, [u32; 4])>,
+    clips: HashMap<String, AnimationClip>,
+}
+
+impl AsepriteSheet {
+    pub fn open(json: impl AsRef<Path>) -> Result<Self, AsepriteError> {
+        let json = json.as_ref();
+        let mut sheet = Self::parse(&std::fs::read_to_string(json)?)?;
+        // The image path is relative to the JSON file
+        sheet.image = json.parent().unwrap_or(Path::new("")).join(&sheet.image);
+        Ok(sheet)
+    }
+
+    pub fn parse(json: &str) -> Result<Self, AsepriteError> {
+        let export: Export = serde_json::from_str(json)?;
+        let frames: Vec<_> = match export.frames {
+            ExportFrames::Array(frames) => frames
+                .into_iter()
+                .map(|frame| (frame.filename, frame.frame))
+                .collect(),
+            ExportFrames::Hash(FrameMap(frames)) => frames,
+        };
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(|(name, frame)| (name, frame.frame.pixels(), frame.duration as f32 / 1000.0))
+            .collect();
+        let size = [export.meta.size.w, export.meta.size.h];
+
+        let mut slices = Vec::new();
+        for slice in export.meta.slices {
+            if frames.iter().any(|(name, _, _)| *name == slice.name) {
+                return Err(AsepriteError::DuplicateName(slice.name));
+            }
+            if let Some(key) = slice.keys.first() {
+                slices.push((slice.name, key.bounds.pixels()));
+            }
+        }
+
+        let mut clips = HashMap::new();
+        for tag in export.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(AsepriteError::BadTag(tag.name));
+            }
+            let clip = tag_clip(&tag, &frames, size);
+            clips.insert(tag.name, clip);
+        }
+
+        Ok(Self {
+            image: export.meta.image,
+            size,
+            frames,
+            slices,
+            clips,
+        })
+    }
+
+    // Where the sheet's PNG is
+    pub fn image(&self) -> &Path {
+        &self.image
+    }
+    // Width and height of the image, in pixels
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+    pub fn frame_names(&self) -> impl Iterator<Item = &str> {
+        self.frames.iter().map(|(name, _, _)| name.as_str())
+    }
+
+    // Every frame (by its name in the export) and slice, in pixels
+    pub fn regions(&self) -> HashMap<String, [u32; 4]> {
+        self.frames
+            .iter()
+            .map(|(name, rect, _)| (name.clone(), *rect))
+            .chain(self.slices.iter().cloned())
+            .collect()
+    }
+
+    pub fn clip(&self, tag: &str) -> Option<&AnimationClip> {
+        self.clips.get(tag)
+    }
+    pub fn clips(&self) -> &HashMap<String, AnimationClip> {
+        &self.clips
+    }
+
+    // Loads the PNG into a TextureAtlas with every region from `regions`
+    pub fn atlas(&self, gpu: &WGPU) -> Result<TextureAtlas, AsepriteError> {
+        let image = image::open(&self.image)?.to_rgba8();
+        Ok(TextureAtlas::from_image(gpu, &image, self.regions())?)
+    }
+}
+
+// One frame tag as a clip. A tag with a repeat count is unrolled and played once; without
+// one it loops forever.
+fn tag_clip(
+    tag: &Tag,
+    frames: &[(String, [u32; 4], f32)],
+    [width, height]: [u32; 2],
+) -> AnimationClip {
+    let frame = |i: usize| {
+        let ([x, y, w, h], duration) = (frames[i].1, frames[i].2);
+        Frame {
+            sheet_region: [
+                x as f32 / width as f32,
+                y as f32 / height as f32,
+                w as f32 / width as f32,
+                h as f32 / height as f32,
+            ],
+            duration,
+        }
+    };
+    let mut order: Vec<usize> = (tag.from..=tag.to).collect();
+    if matches!(
+        tag.direction,
+        Direction::Reverse | Direction::PingpongReverse
+    ) {
+        order.reverse();
+    }
+    let ping_pong = matches!(
+        tag.direction,
+        Direction::Pingpong | Direction::PingpongReverse
+    );
+    match tag.repeat.as_deref().and_then(|n| n.parse::<usize>().ok()) {
+        Some(times) => {
+            // Aseprite counts each pass of a ping-pong as one repeat. Passes after the first
+            // skip the frame the previous one ended on.
+            let mut unrolled = order.clone();
+            for pass in 1..times {
+                if ping_pong {
+                    let back = pass % 2 == 1;
+                    let next: Vec<usize> = if back {
+                        order.iter().rev().skip(1).copied().collect()
+                    } else {
+                        order.iter().skip(1).copied().collect()
+                    };
+                    unrolled.extend(next);
+                } else {
+                    unrolled.extend(order.iter().copied());
+                }
+            }
+            AnimationClip::new(unrolled.into_iter().map(frame).collect(), PlayMode::Once)
+        }
+        None => {
+            let mode = if ping_pong {
+                PlayMode::PingPong
+            } else {
+                PlayMode::Loop
+            };
+            AnimationClip::new(order.into_iter().map(frame).collect(), mode)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+
+    const SAMPLE: &str = include_str!("testdata/walker.json");
+
+    // Sheet regions in the sample are in 16 pixel steps of a 64x32 image
+    fn region(x: u32, y: u32, w: u32, h: u32) -> [f32; 4] {
+        [
+            x as f32 / 64.0,
+            y as f32 / 32.0,
+            w as f32 / 64.0,
+            h as f32 / 32.0,
+        ]
+    }
+
+    fn clip_frames(sheet: &AsepriteSheet, tag: &str) -> Vec<[f32; 4]> {
+        let clip = sheet.clip(tag).unwrap();
+        clip.frames.iter().map(|frame| frame.sheet_region).collect()
+    }
+
+    #[test]
+    fn frames_keep_file_order() {
+        let sheet = AsepriteSheet::parse(SAMPLE).unwrap();
+        let names: Vec<_> = sheet.frame_names().collect();
+        assert_eq!(
+            names,
+            [
+                "walker 0.aseprite",
+                "walker 1.aseprite",
+                "walker 2.aseprite",
+                "walker 3.aseprite",
+                "walker 10.aseprite",
+            ]
+        );
+        assert_eq!(sheet.size(), [64, 32]);
+        assert_eq!(sheet.image(), Path::new("walker.png"));
+    }
+
+    #[test]
+    fn frames_and_slices_become_regions() {
+        let sheet = AsepriteSheet::parse(SAMPLE).unwrap();
+        let regions = sheet.regions();
+        assert_eq!(regions.len(), 6);
+        assert_eq!(regions["walker 2.aseprite"], [32, 0, 16, 16]);
+        assert_eq!(regions["walker 10.aseprite"], [0, 16, 32, 16]);
+        assert_eq!(regions["hitbox"], [36, 2, 8, 12]);
+    }
+
+    #[test]
+    fn forward_tag_loops_with_frame_durations() {
+        let sheet = AsepriteSheet::parse(SAMPLE).unwrap();
+        let walk = sheet.clip("walk").unwrap();
+        assert_eq!(walk.mode, PlayMode::Loop);
+        let durations: Vec<_> = walk.frames.iter().map(|frame| frame.duration).collect();
+        assert_eq!(durations, [0.1, 0.1, 0.15, 0.25]);
+        assert_eq!(
+            clip_frames(&sheet, "walk"),
+            [
+                region(0, 0, 16, 16),
+                region(16, 0, 16, 16),
+                region(32, 0, 16, 16),
+                region(48, 0, 16, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn reverse_and_pingpong_tags() {
+        let sheet = AsepriteSheet::parse(SAMPLE).unwrap();
+        assert_eq!(sheet.clip("back").unwrap().mode, PlayMode::Loop);
+        assert_eq!(
+            clip_frames(&sheet, "back"),
+            [
+                region(32, 0, 16, 16),
+                region(16, 0, 16, 16),
+                region(0, 0, 16, 16),
+            ]
+        );
+        assert_eq!(sheet.clip("turn").unwrap().mode, PlayMode::PingPong);
+        assert_eq!(
+            clip_frames(&sheet, "turn"),
+            [
+                region(16, 0, 16, 16),
+                region(32, 0, 16, 16),
+                region(48, 0, 16, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_count_plays_once() {
+        let sheet = AsepriteSheet::parse(SAMPLE).unwrap();
+        let land = sheet.clip("land").unwrap();
+        assert_eq!(land.mode, PlayMode::Once);
+        assert_eq!(
+            clip_frames(&sheet, "land"),
+            [region(32, 0, 16, 16), region(48, 0, 16, 16)]
+        );
+    }
+
+    #[test]
+    fn array_export() {
+        let json = r#"{
+            "frames": [
+                { "filename": "b", "frame": { "x": 8, "y": 0, "w": 8, "h": 8 }, "duration": 50 },
+                { "filename": "a", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 70 }
+            ],
+            "meta": {
+                "image": "tiny.png",
+                "size": { "w": 16, "h": 8 },
+                "frameTags": [{ "name": "blink", "from": 0, "to": 1, "direction": "pingpong", "repeat": "3" }]
+            }
+        }"#;
+        let sheet = AsepriteSheet::parse(json).unwrap();
+        assert_eq!(sheet.frame_names().collect::<Vec<_>>(), ["b", "a"]);
+        // Three passes: there, back, there again
+        let blink = sheet.clip("blink").unwrap();
+        let xs: Vec<_> = blink.frames.iter().map(|f| f.sheet_region[0]).collect();
+        assert_eq!(xs, [0.5, 0.0, 0.5, 0.0]);
+        assert_eq!(blink.mode, PlayMode::Once);
+    }
+
+    #[test]
+    fn tag_past_the_last_frame_is_an_error() {
+        let json = r#"{
+            "frames": [{ "filename": "a", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 100 }],
+            "meta": {
+                "image": "tiny.png",
+                "size": { "w": 8, "h": 8 },
+                "frameTags": [{ "name": "run", "from": 0, "to": 4 }]
+            }
+        }"#;
+        assert!(matches!(
+            AsepriteSheet::parse(json),
+            Err(AsepriteError::BadTag(name)) if name == "run"
+        ));
+    }
+
+    #[test]
+    fn open_loads_the_image_next_to_the_json() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata/walker.json");
+        let sheet = AsepriteSheet::open(path).unwrap();
+        assert!(sheet.image().ends_with("testdata/walker.png"));
+        let Some(gpu) = test_gpu() else {
+            return;
+        };
+        let atlas = sheet.atlas(&gpu).unwrap();
+        assert_eq!(atlas.region("hitbox"), Some(region(36, 2, 8, 12)));
+        assert_eq!(atlas.texture().size().width, 64);
+    }
+}
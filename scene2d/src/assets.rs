@@ -0,0 +1,208 @@
+use crate::{AtlasBuilder, AtlasError, TextureAtlas, WGPU};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+// A shared, reference-counted asset. Cloning it is cheap and every clone points at the same
+// loaded data, e.g. one wgpu::Texture no matter how many sprite groups use it.
+pub struct Handle<T>(Arc<T>);
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(self.0.clone())
+    }
+}
+
+impl<T> std::ops::Deref for Handle<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Handle<T> {
+    // Wraps something that didn't come from Assets, so it can go wherever a handle is expected
+    pub fn new(asset: T) -> Self {
+        Handle(Arc::new(asset))
+    }
+    // Whether two handles are the same loaded asset
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> From<T> for Handle<T> {
+    fn from(asset: T) -> Self {
+        Handle::new(asset)
+    }
+}
+
+// Loads each file once and hands out handles to it after that. Everything stays loaded until
+// purge_unused is called with no handles left outside the cache.
+#[derive(Default)]
+pub struct Assets {
+    cache: HashMap<(TypeId, PathBuf), Arc<dyn Any + Send + Sync>>,
+    // Atlases packed from several files, which aren't cached under any one path but still get
+    // hot reloaded for as long as something holds them
+    atlases: Vec<Weak<TextureAtlas>>,
+}
+
+// "a/../b.png" and "b.png" should be the same asset
+pub(crate) fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Assets {
+    // The texture at `path`, uploading it the first time it's asked for
+    pub fn texture(
+        &mut self,
+        gpu: &WGPU,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<wgpu::Texture>, image::ImageError> {
+        let path = path.as_ref();
+        self.load(path, |path| {
+            let label = path.to_string_lossy();
+            gpu.load_texture(path, Some(&label))
+                .map(|(texture, _)| texture)
+        })
+    }
+
+    // Any other kind of asset (sounds, say): `load` only runs if nothing of type T has been
+    // loaded from `path` yet.
+    pub fn load<T: Any + Send + Sync, E>(
+        &mut self,
+        path: impl AsRef<Path>,
+        load: impl FnOnce(&Path) -> Result<T, E>,
+    ) -> Result<Handle<T>, E> {
+        let path = path.as_ref();
+        let key = (TypeId::of::<T>(), canonical(path));
+        if let Some(asset) = self.cache.get(&key) {
+            let asset = asset
+                .clone()
+                .downcast::<T>()
+                .expect("cache key includes the asset's type");
+            return Ok(Handle(asset));
+        }
+        let asset = Arc::new(load(path)?);
+        self.cache.insert(key, asset.clone());
+        Ok(Handle(asset))
+    }
+
+    // Packs `builder` into an atlas that hot reloads along with the cached textures
+    pub fn atlas(
+        &mut self,
+        gpu: &WGPU,
+        builder: AtlasBuilder,
+    ) -> Result<Handle<TextureAtlas>, AtlasError> {
+        let atlas = Arc::new(builder.build(gpu)?);
+        self.atlases.retain(|atlas| atlas.strong_count() > 0);
+        self.atlases.push(Arc::downgrade(&atlas));
+        Ok(Handle(atlas))
+    }
+
+    // Every atlas that's still in use, cached or not
+    fn live_atlases(&self) -> Vec<Arc<TextureAtlas>> {
+        let cached = self
+            .cache
+            .values()
+            .filter_map(|asset| asset.clone().downcast::<TextureAtlas>().ok());
+        cached
+            .chain(self.atlases.iter().filter_map(Weak::upgrade))
+            .collect()
+    }
+
+    // Every file a cached texture or a live atlas was loaded from, each once
+    pub(crate) fn watched_paths(&self) -> Vec<PathBuf> {
+        let textures = self
+            .cache
+            .keys()
+            .filter(|(kind, _)| *kind == TypeId::of::<wgpu::Texture>())
+            .map(|(_, path)| path.clone());
+        let atlases = self.live_atlases();
+        let sources = atlases
+            .iter()
+            .flat_map(|atlas| atlas.source_paths().map(Path::to_path_buf));
+        let mut paths: Vec<PathBuf> = textures.chain(sources).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    // Loads `path` again into every texture and atlas that came from it
+    pub(crate) fn reload(&self, gpu: &WGPU, path: &Path) -> Result<(), image::ImageError> {
+        self.reload_texture(gpu, path)?;
+        for atlas in self.live_atlases() {
+            atlas.reload(gpu, path)?;
+        }
+        Ok(())
+    }
+
+    // Loads a cached texture's file again and writes it into the same wgpu::Texture, so every
+    // sprite group already drawing with it picks up the change
+    fn reload_texture(&self, gpu: &WGPU, path: &Path) -> Result<(), image::ImageError> {
+        let Some(texture) = self
+            .cache
+            .get(&(TypeId::of::<wgpu::Texture>(), path.to_path_buf()))
+            .and_then(|asset| asset.downcast_ref::<wgpu::Texture>())
+        else {
+            return Ok(());
+        };
+        let img = image::open(path)?.to_rgba8();
+        let size = texture.size();
+        if img.dimensions() != (size.width, size.height) {
+            log::warn!("{} changed size, restart to see it", path.to_string_lossy());
+            return Ok(());
+        }
+        gpu.write_texture(texture, &img);
+        Ok(())
+    }
+
+    // Drops every asset nobody holds a handle to anymore
+    pub fn purge_unused(&mut self) {
+        self.cache.retain(|_, asset| Arc::strong_count(asset) > 1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+
+    #[test]
+    fn atlases_are_watched_while_they_are_used() {
+        let Some(gpu) = test_gpu() else {
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("assets-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sheet.png");
+        image::RgbaImage::new(8, 8).save(&path).unwrap();
+
+        let mut assets = Assets::default();
+        let mut builder = AtlasBuilder::new();
+        builder
+            .add_file_region("corner", &path, [4, 4, 4, 4])
+            .unwrap();
+        builder
+            .add_image("generated", image::RgbaImage::new(2, 2))
+            .unwrap();
+        let atlas = assets.atlas(&gpu, builder).unwrap();
+        assert_eq!(assets.watched_paths(), [canonical(&path)]);
+        assert!(assets.reload(&gpu, &path).is_ok());
+        // Too small for the region now, which is skipped rather than an error
+        image::RgbaImage::new(2, 2).save(&path).unwrap();
+        assert!(assets.reload(&gpu, &path).is_ok());
+
+        drop(atlas);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(assets.watched_paths().is_empty());
+    }
+}
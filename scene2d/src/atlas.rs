@@ -0,0 +1,520 @@
+use crate::assets::canonical;
+use crate::WGPU;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Manifest(serde_json::Error),
+    // Two images or regions were given the same name
+    DuplicateName(String),
+    // A region in a manifest (or passed to add_region) isn't inside its image
+    OutOfBounds(String),
+    // Everything together doesn't fit in the biggest texture the GPU allows
+    TooBig,
+}
+
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::Io(err) => write!(f, "couldn't read atlas manifest: {}", err),
+            AtlasError::Image(err) => write!(f, "couldn't load atlas image: {}", err),
+            AtlasError::Manifest(err) => write!(f, "bad atlas manifest: {}", err),
+            AtlasError::DuplicateName(name) => write!(f, "atlas region {:?} added twice", name),
+            AtlasError::OutOfBounds(name) => {
+                write!(f, "atlas region {:?} is outside its image", name)
+            }
+            AtlasError::TooBig => write!(f, "atlas images don't fit in one texture"),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<std::io::Error> for AtlasError {
+    fn from(err: std::io::Error) -> Self {
+        AtlasError::Io(err)
+    }
+}
+impl From<image::ImageError> for AtlasError {
+    fn from(err: image::ImageError) -> Self {
+        AtlasError::Image(err)
+    }
+}
+impl From<serde_json::Error> for AtlasError {
+    fn from(err: serde_json::Error) -> Self {
+        AtlasError::Manifest(err)
+    }
+}
+
+// A manifest file names rectangles (x, y, width, height in pixels, from the top left) of one
+// image, e.g.
+//     { "image": "kiiiii.png", "regions": { "platform": [0, 0, 16, 16] } }
+// The image path is relative to the manifest.
+#[derive(serde::Deserialize)]
+struct Manifest {
+    image: PathBuf,
+    regions: HashMap<String, [u32; 4]>,
+}
+
+impl Manifest {
+    fn load(path: &Path) -> Result<(Self, RgbaImage), AtlasError> {
+        let mut manifest: Manifest = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        manifest.image = canonical(&dir.join(&manifest.image));
+        let image = image::open(&manifest.image)?.to_rgba8();
+        for (name, rect) in &manifest.regions {
+            check_bounds(name, &image, *rect)?;
+        }
+        Ok((manifest, image))
+    }
+}
+
+fn check_bounds(name: &str, image: &RgbaImage, [x, y, w, h]: [u32; 4]) -> Result<(), AtlasError> {
+    // Manifests can say anything, so don't let a huge x or w wrap around to look small
+    let right = x.checked_add(w);
+    let bottom = y.checked_add(h);
+    if right.is_none_or(|right| right > image.width())
+        || bottom.is_none_or(|bottom| bottom > image.height())
+    {
+        return Err(AtlasError::OutOfBounds(name.to_string()));
+    }
+    Ok(())
+}
+
+// The part of an image file (x, y, width, height in pixels) some of an atlas was copied from
+#[derive(Clone, Debug, PartialEq)]
+struct Source {
+    path: PathBuf,
+    rect: [u32; 4],
+}
+
+impl Source {
+    fn whole(path: &Path, image: &RgbaImage) -> Self {
+        Source {
+            path: canonical(path),
+            rect: [0, 0, image.width(), image.height()],
+        }
+    }
+}
+
+// One texture holding lots of named images. Look sprites up by name instead of working out
+// sheet_region fractions by hand.
+pub struct TextureAtlas {
+    texture: wgpu::Texture,
+    size: [u32; 2],
+    regions: HashMap<String, [u32; 4]>,
+    // What came from files, and where in the texture it went, for hot reloading
+    sources: Vec<(Source, [u32; 2])>,
+}
+
+impl TextureAtlas {
+    // Uses a prebuilt atlas as-is: its image becomes the texture and its regions are the names
+    pub fn load(gpu: &WGPU, manifest: impl AsRef<Path>) -> Result<Self, AtlasError> {
+        let (manifest, image) = Manifest::load(manifest.as_ref())?;
+        let mut atlas = Self::from_image(gpu, &image, manifest.regions)?;
+        atlas.sources = vec![(Source::whole(&manifest.image, &image), [0, 0])];
+        Ok(atlas)
+    }
+    // An image that's already laid out, with its regions in pixels
+    pub fn from_image(
+        gpu: &WGPU,
+        image: &RgbaImage,
+        regions: HashMap<String, [u32; 4]>,
+    ) -> Result<Self, AtlasError> {
+        for (name, rect) in &regions {
+            check_bounds(name, image, *rect)?;
+        }
+        Ok(Self {
+            texture: gpu.create_texture(image, Some("atlas")),
+            size: [image.width(), image.height()],
+            regions,
+            sources: vec![],
+        })
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    // The named image's sheet_region, ready for GPUSprite
+    pub fn region(&self, name: &str) -> Option<[f32; 4]> {
+        let [x, y, w, h] = *self.regions.get(name)?;
+        let [width, height] = [self.size[0] as f32, self.size[1] as f32];
+        Some([
+            x as f32 / width,
+            y as f32 / height,
+            w as f32 / width,
+            h as f32 / height,
+        ])
+    }
+    // Where the named image is in the texture, in pixels
+    pub fn pixel_region(&self, name: &str) -> Option<[u32; 4]> {
+        self.regions.get(name).copied()
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.regions.keys().map(String::as_str)
+    }
+
+    // Every file this atlas copied pixels from
+    pub(crate) fn source_paths(&self) -> impl Iterator<Item = &Path> {
+        self.sources.iter().map(|(source, _)| source.path.as_path())
+    }
+    // Copies whatever came from `path` into the texture again
+    pub(crate) fn reload(&self, gpu: &WGPU, path: &Path) -> Result<(), image::ImageError> {
+        let mut image = None;
+        for (source, at) in self
+            .sources
+            .iter()
+            .filter(|(source, _)| source.path == path)
+        {
+            let image = match &image {
+                Some(image) => image,
+                None => image.insert(image::open(path)?.to_rgba8()),
+            };
+            let [x, y, w, h] = source.rect;
+            if check_bounds("", image, source.rect).is_err() {
+                log::warn!("{} got smaller, restart to see it", path.to_string_lossy());
+                return Ok(());
+            }
+            let pixels = image::imageops::crop_imm(image, x, y, w, h).to_image();
+            gpu.write_texture_at(&self.texture, *at, &pixels);
+        }
+        Ok(())
+    }
+}
+
+// Collects images and packs them into a TextureAtlas. Images added from files (add_file,
+// add_file_region and add_manifest) are hot reloaded when the atlas is built with Engine::atlas.
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage, Option<Source>)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) -> Result<(), AtlasError> {
+        self.push(name, image, None)
+    }
+    pub fn add_file(&mut self, name: &str, path: impl AsRef<Path>) -> Result<(), AtlasError> {
+        let path = path.as_ref();
+        let image = image::open(path)?.to_rgba8();
+        let source = Source::whole(path, &image);
+        self.push(name, image, Some(source))
+    }
+    // Adds part of an image file, like add_region
+    pub fn add_file_region(
+        &mut self,
+        name: &str,
+        path: impl AsRef<Path>,
+        rect: [u32; 4],
+    ) -> Result<(), AtlasError> {
+        let path = path.as_ref();
+        self.add_source_region(name, &image::open(path)?.to_rgba8(), path, rect)
+    }
+    // Adds just part of an image (x, y, width, height in pixels from the top left)
+    pub fn add_region(
+        &mut self,
+        name: &str,
+        image: &RgbaImage,
+        rect: [u32; 4],
+    ) -> Result<(), AtlasError> {
+        check_bounds(name, image, rect)?;
+        let [x, y, w, h] = rect;
+        self.add_image(
+            name,
+            image::imageops::crop_imm(image, x, y, w, h).to_image(),
+        )
+    }
+    // Adds every region named in a manifest file
+    pub fn add_manifest(&mut self, path: impl AsRef<Path>) -> Result<(), AtlasError> {
+        let (manifest, image) = Manifest::load(path.as_ref())?;
+        // Sorted so the packing doesn't depend on HashMap order
+        let mut regions: Vec<_> = manifest.regions.into_iter().collect();
+        regions.sort();
+        for (name, rect) in regions {
+            self.add_source_region(&name, &image, &manifest.image, rect)?;
+        }
+        Ok(())
+    }
+
+    fn add_source_region(
+        &mut self,
+        name: &str,
+        image: &RgbaImage,
+        path: &Path,
+        rect: [u32; 4],
+    ) -> Result<(), AtlasError> {
+        check_bounds(name, image, rect)?;
+        let [x, y, w, h] = rect;
+        let source = Source {
+            path: canonical(path),
+            rect,
+        };
+        self.push(
+            name,
+            image::imageops::crop_imm(image, x, y, w, h).to_image(),
+            Some(source),
+        )
+    }
+    fn push(
+        &mut self,
+        name: &str,
+        image: RgbaImage,
+        source: Option<Source>,
+    ) -> Result<(), AtlasError> {
+        if self.images.iter().any(|(existing, _, _)| existing == name) {
+            return Err(AtlasError::DuplicateName(name.to_string()));
+        }
+        self.images.push((name.to_string(), image, source));
+        Ok(())
+    }
+
+    pub fn build(self, gpu: &WGPU) -> Result<TextureAtlas, AtlasError> {
+        let max_size = gpu.device.limits().max_texture_dimension_2d;
+        // Nothing bigger than a texture fits in one. Checking that up front also keeps the
+        // packer's sums well inside a u32.
+        if self
+            .images
+            .iter()
+            .any(|(_, img, _)| img.width() > max_size || img.height() > max_size)
+        {
+            return Err(AtlasError::TooBig);
+        }
+        // Tallest first packs rows much more tightly
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].1.height()));
+        // Start from the smallest square that could possibly fit, then keep doubling
+        let area = self
+            .images
+            .iter()
+            .try_fold(0u32, |area, (_, img, _)| {
+                area.checked_add(img.width().checked_mul(img.height())?)
+            })
+            .ok_or(AtlasError::TooBig)?;
+        let mut size = [1u32, 1u32];
+        // A size too big for a u32 area is bigger than any area
+        while size[0].checked_mul(size[1]).is_some_and(|s| s < area) {
+            size = grow(size).ok_or(AtlasError::TooBig)?;
+        }
+        let placements = loop {
+            if size[0] > max_size || size[1] > max_size {
+                return Err(AtlasError::TooBig);
+            }
+            let mut packer = ShelfPacker::new(size, 1);
+            let placed: Option<Vec<_>> = order
+                .iter()
+                .map(|&i| packer.allocate(self.images[i].1.width(), self.images[i].1.height()))
+                .collect();
+            match placed {
+                Some(placed) => break placed,
+                None => size = grow(size).ok_or(AtlasError::TooBig)?,
+            }
+        };
+
+        let mut sheet = RgbaImage::new(size[0], size[1]);
+        let mut regions = HashMap::new();
+        let mut sources = vec![];
+        for (&i, [x, y]) in order.iter().zip(placements) {
+            let (name, image, source) = &self.images[i];
+            image::imageops::replace(&mut sheet, image, x as i64, y as i64);
+            regions.insert(name.clone(), [x, y, image.width(), image.height()]);
+            if let Some(source) = source {
+                sources.push((source.clone(), [x, y]));
+            }
+        }
+        Ok(TextureAtlas {
+            texture: gpu.create_texture(&sheet, Some("atlas")),
+            size,
+            regions,
+            sources,
+        })
+    }
+}
+
+// Doubles the shorter side
+// None once it can't double any more
+fn grow([w, h]: [u32; 2]) -> Option<[u32; 2]> {
+    if w <= h {
+        Some([w.checked_mul(2)?, h])
+    } else {
+        Some([w, h.checked_mul(2)?])
+    }
+}
+
+// Places rectangles left to right in rows, starting a new row under the tallest thing in the
+// current one when it runs out of room.
+pub(crate) struct ShelfPacker {
+    size: [u32; 2],
+    padding: u32,
+    cursor: [u32; 2],
+    row_height: u32,
+}
+
+impl ShelfPacker {
+    // `padding` empty pixels are kept around everything
+    pub(crate) fn new(size: [u32; 2], padding: u32) -> Self {
+        Self {
+            size,
+            padding,
+            cursor: [padding, padding],
+            row_height: 0,
+        }
+    }
+
+    // Top left corner of the space for a w*h rectangle, or None if it's full
+    pub(crate) fn allocate(&mut self, w: u32, h: u32) -> Option<[u32; 2]> {
+        if self.cursor[0] + w + self.padding > self.size[0] {
+            self.cursor = [
+                self.padding,
+                self.cursor[1] + self.row_height + self.padding,
+            ];
+            self.row_height = 0;
+        }
+        if w + 2 * self.padding > self.size[0] || self.cursor[1] + h + self.padding > self.size[1] {
+            return None;
+        }
+        let at = self.cursor;
+        self.cursor[0] += w + self.padding;
+        self.row_height = self.row_height.max(h);
+        Some(at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+
+    fn solid(w: u32, h: u32) -> RgbaImage {
+        RgbaImage::from_pixel(w, h, image::Rgba([255, 0, 0, 255]))
+    }
+
+    #[test]
+    fn shelves_fill_left_to_right_then_start_a_new_row() {
+        let mut packer = ShelfPacker::new([32, 32], 1);
+        assert_eq!(packer.allocate(10, 8), Some([1, 1]));
+        assert_eq!(packer.allocate(10, 4), Some([12, 1]));
+        // 23 + 10 + 1 padding doesn't fit in 32, so it goes under the taller of the two
+        assert_eq!(packer.allocate(10, 4), Some([1, 10]));
+        assert_eq!(packer.allocate(30, 16), Some([1, 15]));
+        assert_eq!(packer.allocate(1, 1), None);
+    }
+
+    #[test]
+    fn things_bigger_than_the_packer_never_fit() {
+        assert_eq!(ShelfPacker::new([16, 16], 1).allocate(15, 2), None);
+        assert_eq!(ShelfPacker::new([16, 16], 1).allocate(2, 15), None);
+        assert_eq!(ShelfPacker::new([16, 16], 0).allocate(16, 16), Some([0, 0]));
+    }
+
+    #[test]
+    fn names_can_only_be_used_once() {
+        let mut builder = AtlasBuilder::new();
+        builder.add_image("a", solid(2, 2)).unwrap();
+        let err = builder.add_image("a", solid(4, 4)).unwrap_err();
+        assert!(matches!(err, AtlasError::DuplicateName(name) if name == "a"));
+        let err = builder
+            .add_region("a", &solid(4, 4), [0, 0, 2, 2])
+            .unwrap_err();
+        assert!(matches!(err, AtlasError::DuplicateName(_)));
+    }
+
+    #[test]
+    fn regions_must_be_inside_their_image() {
+        let image = solid(16, 8);
+        assert!(check_bounds("ok", &image, [0, 0, 16, 8]).is_ok());
+        assert!(check_bounds("ok", &image, [15, 7, 1, 1]).is_ok());
+        for rect in [
+            [0, 0, 17, 8],
+            [0, 1, 16, 8],
+            [u32::MAX, 0, 2, 2],
+            [0, 2, 1, u32::MAX],
+        ] {
+            let err = check_bounds("bad", &image, rect).unwrap_err();
+            assert!(matches!(err, AtlasError::OutOfBounds(_)), "{:?}", rect);
+        }
+    }
+
+    #[test]
+    fn manifests_with_regions_outside_the_image_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("atlas-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        solid(16, 16).save(dir.join("sheet.png")).unwrap();
+        let manifest = dir.join("sheet.json");
+        std::fs::write(
+            &manifest,
+            r#"{ "image": "sheet.png", "regions": { "all": [0, 0, 16, 16] } }"#,
+        )
+        .unwrap();
+        let mut builder = AtlasBuilder::new();
+        builder.add_manifest(&manifest).unwrap();
+        assert_eq!(builder.images.len(), 1);
+
+        std::fs::write(
+            &manifest,
+            r#"{ "image": "sheet.png", "regions": { "wrap": [4294967295, 0, 2, 2] } }"#,
+        )
+        .unwrap();
+        let err = AtlasBuilder::new().add_manifest(&manifest).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(err, AtlasError::OutOfBounds(name) if name == "wrap"));
+    }
+
+    #[test]
+    fn build_grows_until_everything_fits() {
+        let Some(gpu) = test_gpu() else {
+            return;
+        };
+        let mut builder = AtlasBuilder::new();
+        for name in ["a", "b", "c", "d"] {
+            builder.add_image(name, solid(16, 16)).unwrap();
+        }
+        builder.add_image("tall", solid(4, 20)).unwrap();
+        let atlas = builder.build(&gpu).unwrap();
+        // Five images take 1104 pixels, so 32x32 is too small, and at 64x32 the last square
+        // fits neither beside the others nor under the 20 pixel tall row
+        assert_eq!(atlas.size, [64, 64]);
+        let rects: Vec<[u32; 4]> = ["a", "b", "c", "d", "tall"]
+            .iter()
+            .map(|name| atlas.pixel_region(name).unwrap())
+            .collect();
+        assert_eq!(rects[4][2..], [4, 20]);
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a[0] + a[2] <= 64 && a[1] + a[3] <= 64);
+            for b in &rects[i + 1..] {
+                let apart = a[0] + a[2] <= b[0]
+                    || b[0] + b[2] <= a[0]
+                    || a[1] + a[3] <= b[1]
+                    || b[1] + b[3] <= a[1];
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        assert_eq!(atlas.region("a").unwrap()[2..], [0.25, 0.25]);
+    }
+
+    #[test]
+    fn build_fails_when_nothing_is_big_enough() {
+        let Some(gpu) = test_gpu() else {
+            return;
+        };
+        let max = gpu.device.limits().max_texture_dimension_2d;
+        let mut builder = AtlasBuilder::new();
+        builder.add_image("wide", solid(max + 1, 1)).unwrap();
+        assert!(matches!(builder.build(&gpu), Err(AtlasError::TooBig)));
+    }
+
+    #[test]
+    fn sizes_stop_growing_before_they_overflow() {
+        assert_eq!(grow([4, 4]), Some([8, 4]));
+        assert_eq!(grow([8, 4]), Some([8, 8]));
+        assert_eq!(grow([1 << 31, 1 << 31]), None);
+        assert_eq!(grow([1 << 31, 1 << 30]), Some([1 << 31, 1 << 31]));
+    }
+}
@@ -1,45 +1,30 @@
-use crate::{input, sprite::SpriteRender, Game, WGPU};
+use crate::{
+    input,
+    reload::{Watcher, SHADER_PATH},
+    sprite::SpriteRender,
+    timestep::FixedTimestep,
+    Assets, AtlasBuilder, AtlasError, Game, GilrsGamepads, Handle, TextureAtlas, WGPU,
+};
 //use std::thread;
 use winit::{
-    event::{Event, VirtualKeyCode, WindowEvent},
+    event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
 
-use kira::{
-    manager::{
-        backend::DefaultBackend, // changed to default backend
-        AudioManager,
-        AudioManagerSettings,
-    },
-    sound::static_sound::{StaticSoundData, StaticSoundSettings},
-};
-//TODO IF JUMPING YOU CAN"T JUMP AGAIN UNTIL A CERTAIN PERIOD OF TIME WHY IS THAT
-
 pub struct Engine {
     pub gpu: WGPU,
     pub sprites: SpriteRender,
     pub input: input::Input,
-    pub is_jumping: bool,
-    pub leftis_jumping: bool,
-    pub velocity_y: f32,
-    pub leftvelocity_y: f32,
-    pub gravity: f32,
-    pub score: usize,
-    pub single_player: bool,
-    pub sub_score: usize,
-    pub audio_cues: bool,
-    pub left_keyboard: bool,
-    pub high_contrast: bool,
-    pub audio: AudioManager,
+    pub assets: Assets,
+    // Some while hot reloading is on
+    watcher: Option<Watcher>,
+    timestep: FixedTimestep,
+    quit: bool,
 }
 
-pub struct Keyboard {
-    pub left: VirtualKeyCode,
-    pub right: VirtualKeyCode,
-    pub up: VirtualKeyCode,
-    pub down: VirtualKeyCode,
-}
+// Game::update runs this many times a second unless the game asks for something else
+const DEFAULT_TICK_RATE: f32 = 60.0;
 
 impl Engine {
     pub fn start(event_loop: EventLoop<()>, window: Window, game: impl Game + 'static) {
@@ -71,51 +56,23 @@ impl Engine {
     async fn run(event_loop: EventLoop<()>, window: Window, mut game: impl Game + 'static) {
         let gpu = WGPU::new(&window).await;
         let sprites = SpriteRender::new(&gpu);
-        let is_jumping = false;
-        let leftis_jumping = false;
-        let velocity_y = 0.0;
-        let leftvelocity_y = 0.0;
-        let gravity = -0.8; // Adjust this. Negative as it will pull the sprite down.
-        let score = 0;
-        let input = input::Input::default();
-        let single_player = false;
-        let sub_score = 0;
-        let mut already_removed_multi: bool = false;
-        let mut options_shown = false;
-        let audio_cues = false;
-        let left_keyboard = false;
-        let high_contrast = false;
-        let audio = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default()).unwrap();
+        let mut input = input::Input::default();
+        input.handle_resize(window.inner_size());
+        if let Some(gamepads) = GilrsGamepads::new() {
+            input.set_gamepad_backend(gamepads);
+        }
         let mut engine = Engine {
             gpu,
             sprites,
             input,
-            is_jumping,
-            leftis_jumping,
-            velocity_y,
-            leftvelocity_y,
-            gravity,
-            score,
-            single_player,
-            sub_score,
-            audio_cues,
-            left_keyboard,
-            high_contrast,
-            audio,
-        };
-
-        let mut keyboard = Keyboard {
-            left: VirtualKeyCode::Left,
-            right: VirtualKeyCode::Right,
-            up: VirtualKeyCode::Up,
-            down: VirtualKeyCode::Down,
+            assets: Assets::default(),
+            watcher: None,
+            timestep: FixedTimestep::new(DEFAULT_TICK_RATE),
+            quit: false,
         };
 
         game.init(&mut engine).await;
-        //let mut time = std::time::Instant::now();
-        let mut p1_speed: f32 = 3.0;
-        let mut p2_speed = 3.0; // This is the movement speed that will be affected when using powerups
-        let mut frames = 0;
+        let mut last_frame = std::time::Instant::now();
 
         event_loop.run(move |event, _, control_flow| {
             // By default, tell the windowing system that there's no more work to do
@@ -140,6 +97,7 @@ impl Engine {
                 } => {
                     // Reconfigure the surface with the new size
                     engine.gpu.resize(size);
+                    engine.input.handle_resize(size);
                     // On MacOS the window needs to be redrawn manually after resizing
                     window.request_redraw();
                 }
@@ -150,731 +108,73 @@ impl Engine {
                 } => {
                     engine.input.handle_key_event(key_ev);
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseInput { state, button, .. },
+                    ..
+                } => {
+                    engine.input.handle_mouse_button(state, button);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => {
+                    engine.input.handle_mouse_move(position);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseWheel { delta, .. },
+                    ..
+                } => {
+                    engine.input.handle_mouse_wheel(delta);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorEntered { .. },
+                    ..
+                } => {
+                    engine.input.handle_cursor_inside(true);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorLeft { .. },
+                    ..
+                } => {
+                    engine.input.handle_cursor_inside(false);
+                }
 
                 Event::RedrawRequested(_) => {
-                    //Multiple 60 by the amount of seconds
-                    if frames > 420 {
-                        //Slight Problem: If you get a powerup close to 6 seconds, the powerup will be removed immediatly
-
-                        frames = 0;
-                        p1_speed = 3.0;
-                        p2_speed = 3.0;
-                        //Refresh the sprites after they run out
-                        engine.sprites.respawn_powerup(5);
-                    }
-
-                    // set high contrast
-                    if engine
-                        .input
-                        .is_key_pressed(winit::event::VirtualKeyCode::Key1)
-                        && options_shown
-                    {
-                        engine.high_contrast = !engine.high_contrast;
-                        if engine.high_contrast {
-                            engine.sprites.set_screen_size_sprite(15, 0, [32.0, 32.0])
-                        } else {
-                            let old_region = engine.sprites.get_sprites(15)[0].screen_region;
-                            engine.sprites.zero_sprite(old_region, 15, 0);
-                        }
-                    }
-
-                    // set audio cues
-                    if engine
-                        .input
-                        .is_key_pressed(winit::event::VirtualKeyCode::Key2)
-                        && options_shown
-                    {
-                        engine.audio_cues = !engine.audio_cues;
-                        if engine.audio_cues {
-                            engine.sprites.set_screen_size_sprite(15, 1, [32.0, 32.0])
-                        } else {
-                            let old_region = engine.sprites.get_sprites(15)[1].screen_region;
-                            engine.sprites.zero_sprite(old_region, 15, 1);
-                        }
-                    }
-
-                    // set left keyboard
-                    if engine
-                        .input
-                        .is_key_pressed(winit::event::VirtualKeyCode::Key3)
-                        && options_shown
-                    {
-                        engine.left_keyboard = !engine.left_keyboard;
-                        if engine.left_keyboard {
-                            engine.sprites.set_screen_size_sprite(15, 2, [32.0, 32.0]);
-                            keyboard = Keyboard {
-                                left: VirtualKeyCode::A,
-                                right: VirtualKeyCode::D,
-                                up: VirtualKeyCode::W,
-                                down: VirtualKeyCode::S,
-                            };
-                        } else {
-                            let old_region = engine.sprites.get_sprites(15)[2].screen_region;
-                            engine.sprites.zero_sprite(old_region, 15, 2);
-                            keyboard = Keyboard {
-                                left: VirtualKeyCode::Left,
-                                right: VirtualKeyCode::Right,
-                                up: VirtualKeyCode::Up,
-                                down: VirtualKeyCode::Down,
-                            };
-                        }
-                    }
-
-                    if engine
-                        .input
-                        .is_key_down(winit::event::VirtualKeyCode::Return)
-                    {
-                        // PLAY
-                        if engine.sprites.get_sprites(10)[0].screen_region[1] == 400.0 {
-                            engine.single_player = true;
-                        }
-
-                        if !engine.single_player {
-                            if !options_shown {
-                                engine
-                                    .sprites
-                                    .set_screen_size_sprite(14, 0, [1024.0, 768.0]);
-                                let options = vec![
-                                    engine.high_contrast,
-                                    engine.audio_cues,
-                                    engine.left_keyboard,
-                                ];
-                                for i in 0..3 {
-                                    if options[i] {
-                                        engine.sprites.set_screen_size_sprite(15, i, [32.0, 32.0]);
-                                    }
-                                }
-                                options_shown = true;
-                            }
-                        } else {
-                            let old_region = engine.sprites.get_sprites(6)[0].screen_region;
-                            engine.sprites.zero_sprite(old_region, 6, 0);
-
-                            //platformer
-                            for i in 0..10 {
-                                engine.sprites.zero_sprite(old_region, 7, i);
-                            }
-                            for i in 0..7 {
-                                engine.sprites.zero_sprite(old_region, 8, i);
-                            }
-                            for i in 0..4 {
-                                engine.sprites.zero_sprite(old_region, 9, i);
-                            }
-
-                            engine.sprites.zero_sprite(old_region, 10, 0);
-
-                            if !already_removed_multi {
-                                // zero out multiplayer stuff behind title screen
-                                let old_region = engine.sprites.get_sprites(1)[0].screen_region;
-                                engine.sprites.zero_sprite(old_region, 2, 0);
-                                let old_region = engine.sprites.get_sprites(0)[0].sheet_region;
-                                engine.sprites.update_sprite(
-                                    [old_region[0], old_region[1], 0.75, old_region[3]],
-                                    0,
-                                );
-                                let new_region = [150.0, 85.0, 64.0, 64.0];
-                                engine.sprites.update_position(new_region, 3);
-                                engine.sprites.update_sprite([0.0, 0.0, 0.0, 0.0], 5);
-                                already_removed_multi = true;
-                            }
-                        }
-                    }
-
-                    if engine
-                        .input
-                        .is_key_down(winit::event::VirtualKeyCode::Escape)
-                        && options_shown
-                    {
-                        let mut old_region = engine.sprites.get_sprites(14)[0].screen_region;
-                        engine.sprites.zero_sprite(old_region, 14, 0);
-                        for i in 0..3 {
-                            old_region = engine.sprites.get_sprites(15)[i].screen_region;
-                            engine.sprites.zero_sprite(old_region, 15, i);
-                        }
-                        options_shown = false;
-                    }
-
-                    if engine.input.is_key_down(keyboard.down) {
-                        let old_position = engine.sprites.get_sprites(10)[0].screen_region;
-                        engine
-                            .sprites
-                            .update_position([200.0, 400.0, old_position[2], old_position[3]], 10);
-                    }
-                    if engine.input.is_key_down(keyboard.up) {
-                        let old_position = engine.sprites.get_sprites(10)[0].screen_region;
-                        engine
-                            .sprites
-                            .update_position([200.0, 500.0, old_position[2], old_position[3]], 10);
-                    }
-
-                    if engine.input.is_key_down(keyboard.up) && !engine.is_jumping {
-                        //engine.sprites.update_sprite_score([0.0, 0.54545456, 0.11111111, 0.09090909], 4, 0);
-                        engine.is_jumping = true;
-                        engine.velocity_y = 20.0; // This will be the upward force or the initial jump velocity. Adjust as needed.
-                        let jump = StaticSoundData::from_file(
-                            "scene2d/src/musiccontent/jump.mp3",
-                            StaticSoundSettings::default(),
-                        )
-                        .unwrap();
-
-                        engine.audio.play(jump);
-                    }
-
-                    if engine.leftis_jumping {
-                        let mut the_move = true;
-                        let old_region = engine.sprites.get_sprites(2)[0].screen_region;
-                        engine.leftvelocity_y += engine.gravity; // Apply gravity to velocity
-                        let new_y = old_region[1] + engine.leftvelocity_y;
-
-                        let sprite_x = old_region[0];
-                        let sprite_xw = old_region[0] + old_region[2];
-                        let sprite_y = new_y;
-                        let sprite_yh = new_y + old_region[3];
-
-                        for i in 0..4 {
-                            if (sprite_xw - 5.0 > engine.sprites.get_sprites(1)[i].screen_region[0])
-                                && (sprite_x + 5.0
-                                    < engine.sprites.get_sprites(1)[i].screen_region[0]
-                                        + engine.sprites.get_sprites(1)[i].screen_region[2])
-                                && (sprite_yh > engine.sprites.get_sprites(1)[i].screen_region[1])
-                                && (sprite_y
-                                    < engine.sprites.get_sprites(1)[i].screen_region[1]
-                                        + engine.sprites.get_sprites(1)[i].screen_region[3])
-                            {
-                                the_move = false;
-                            }
-                        }
-
-                        if !engine.single_player {
-                            if ((sprite_xw - 5.0 > 150.0) && (sprite_x + 5.0 < (150.0 + 64.0)))
-                                && (sprite_y < (130.0 + 64.0))
-                            {
-                                the_move = false;
-                            }
-                            if ((sprite_xw - 5.0 > 850.0) && (sprite_x + 5.0 < (850.0 + 64.0)))
-                                && (sprite_y < (130.0 + 64.0))
-                            {
-                                the_move = false;
-                            }
-                        }
-
-                        if the_move {
-                            let new_region = [old_region[0], new_y, old_region[2], old_region[3]];
-                            engine.sprites.update_position(new_region, 2);
-                        }
-
-                        // Check if the sprite has landed.
-                        // Assuming 85.0 is ground level.
-                        if new_y <= 85.0
-                            || (sprite_yh > engine.sprites.get_sprites(1)[0].screen_region[1] - 3.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[0].screen_region[1] + 3.0)
-                            || (sprite_yh > engine.sprites.get_sprites(1)[1].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[1].screen_region[1] + 1.0)
-                            || (sprite_yh > engine.sprites.get_sprites(1)[2].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[2].screen_region[1] + 1.0)
-                            || (sprite_yh > engine.sprites.get_sprites(1)[3].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[3].screen_region[1] + 1.0)
-                        {
-                            engine.leftis_jumping = false;
-                            engine.leftvelocity_y = 0.0;
-                        }
-                    }
-
-                    if engine.is_jumping {
-                        let mut the_move = true;
-                        let old_region = engine.sprites.get_sprites(3)[0].screen_region;
-                        engine.velocity_y += engine.gravity; // Apply gravity to velocity
-                        let new_y = old_region[1] + engine.velocity_y;
-
-                        let sprite_x = old_region[0];
-                        let sprite_xw = old_region[0] + old_region[2];
-                        let sprite_y = new_y;
-                        let sprite_yh = new_y + old_region[3];
-
-                        for i in 0..4 {
-                            if (sprite_xw - 5.0 > engine.sprites.get_sprites(1)[i].screen_region[0])
-                                && (sprite_x + 5.0
-                                    < engine.sprites.get_sprites(1)[i].screen_region[0]
-                                        + engine.sprites.get_sprites(1)[i].screen_region[2])
-                                && (sprite_yh > engine.sprites.get_sprites(1)[i].screen_region[1])
-                                && (sprite_y
-                                    < engine.sprites.get_sprites(1)[i].screen_region[1]
-                                        + engine.sprites.get_sprites(1)[i].screen_region[3])
-                            {
-                                the_move = false;
-                            }
-                        }
-                        if !engine.single_player {
-                            if ((sprite_xw - 5.0 > 150.0) && (sprite_x + 5.0 < (150.0 + 64.0)))
-                                && (sprite_y < (130.0 + 64.0))
-                            {
-                                the_move = false;
-                            }
-                            if ((sprite_xw - 5.0 > 850.0) && (sprite_x + 5.0 < (850.0 + 64.0)))
-                                && (sprite_y < (130.0 + 64.0))
-                            {
-                                the_move = false;
-                            }
+                    // Run as many fixed-length ticks as fit in the time since the last frame, so
+                    // the simulation goes at the same speed whatever the monitor's refresh rate
+                    let now = std::time::Instant::now();
+                    engine
+                        .timestep
+                        .advance(now.duration_since(last_frame).as_secs_f32());
+                    last_frame = now;
+
+                    engine.hot_reload();
+                    engine.input.poll_gamepads();
+                    while engine.timestep.tick() {
+                        let dt = engine.timestep.step();
+                        game.update(&mut engine, dt);
+                        // Each press is seen by exactly one tick, even when a frame has none
+                        engine.input.next_frame();
+                        if engine.quit {
+                            break;
                         }
-                        if the_move {
-                            let new_region = [old_region[0], new_y, old_region[2], old_region[3]];
-                            engine.sprites.update_position(new_region, 3);
-                        }
-
-                        // Check if the sprite has landed.a
-                        // Assuming 85.0 is ground level.
-                        if new_y <= 85.0
-                            || (sprite_yh > engine.sprites.get_sprites(1)[0].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[0].screen_region[1] + 1.0)
-                            || (sprite_yh > engine.sprites.get_sprites(1)[1].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[1].screen_region[1] + 1.0)
-                            || (sprite_yh > engine.sprites.get_sprites(1)[2].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[2].screen_region[1] + 1.0)
-                            || (sprite_yh > engine.sprites.get_sprites(1)[3].screen_region[1] - 1.0
-                                && sprite_yh
-                                    < engine.sprites.get_sprites(1)[3].screen_region[1] + 1.0)
-                        {
-                            engine.is_jumping = false;
-                            engine.velocity_y = 0.0;
-                            let jump = StaticSoundData::from_file(
-                                "scene2d/src/musiccontent/landed.mp3",
-                                StaticSoundSettings::default().volume(2.5),
-                            )
-                            .unwrap();
-
-                            engine.audio.play(jump);
-                        }
-                    }
-
-                    if engine.input.is_key_down(keyboard.right) {
-                        if engine.single_player {
-                            engine.sub_score += 1;
-                            if engine.sub_score == 5 {
-                                engine.score += 1;
-                                let score_digits = engine.sprites.update_score(engine.score);
-
-                                engine.sprites.update_sprite_score(score_digits[0], 4, 6);
-                                engine.sprites.update_sprite_score(score_digits[1], 4, 7);
-                                engine.sprites.update_sprite_score(score_digits[2], 4, 8);
-
-                                engine.sub_score = 0;
-                            }
-                        }
-
-                        let new_sheet_pos = [0.0, 16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0];
-                        engine.sprites.update_sprite(new_sheet_pos, 3);
-                        if engine.single_player {
-                            let mut the_move = true;
-                            let new_region = engine.sprites.get_sprites(3)[0].screen_region;
-
-                            let sprite_x = new_region[0];
-                            let sprite_xw = new_region[0] + new_region[2];
-                            let sprite_y = new_region[1];
-                            let sprite_yh = new_region[1] + new_region[3];
-
-                            for i in 0..4 {
-                                if (sprite_xw - 5.0
-                                    > engine.sprites.get_sprites(1)[i].screen_region[0])
-                                    && (sprite_x + 5.0
-                                        < engine.sprites.get_sprites(1)[i].screen_region[0]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[2])
-                                    && (sprite_yh
-                                        > engine.sprites.get_sprites(1)[i].screen_region[1])
-                                    && (sprite_y
-                                        < engine.sprites.get_sprites(1)[i].screen_region[1]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[3])
-                                {
-                                    the_move = false;
-                                }
-                            }
-
-                            if the_move {
-                                let old_sheet_pos = engine.sprites.get_sprites(0)[0].sheet_region;
-                                engine.sprites.update_sprite(
-                                    [
-                                        old_sheet_pos[0] + (p2_speed / 3000.0),
-                                        old_sheet_pos[1],
-                                        0.75,
-                                        old_sheet_pos[3],
-                                    ],
-                                    0,
-                                );
-                                if old_sheet_pos[0] + p2_speed / 3000.0 > 1.0 {
-                                    engine.sprites.update_sprite(
-                                        [-1.0, old_sheet_pos[1], 0.75, old_sheet_pos[3]],
-                                        0,
-                                    );
-                                }
-                                for i in 0..4 {
-                                    let current = engine.sprites.get_sprites(1)[i].screen_region;
-                                    let block = engine.sprites.get_sprite_mut(1, i);
-                                    block.screen_region =
-                                        [current[0] - p2_speed, current[1], current[2], current[3]];
-                                    if current[0] - p2_speed < -64.0 {
-                                        block.screen_region =
-                                            [1084.0, current[1], current[2], current[3]];
-                                    }
-                                }
-                            } else {
-                                engine.is_jumping = false;
-                            }
-                        } else {
-                            //Technically 0 Should always be the background
-                            //2 should always be the sprite until i change it
-                            let old_region = engine.sprites.get_sprites(3)[0].screen_region;
-                            let new_region = [
-                                old_region[0] + p2_speed,
-                                old_region[1],
-                                old_region[2],
-                                old_region[3],
-                            ];
-
-                            let mut the_move = true;
-
-                            let sprite_x = new_region[0];
-                            let sprite_xw = new_region[0] + new_region[2];
-                            let sprite_y = new_region[1];
-                            let sprite_yh = new_region[1] + new_region[3];
-
-                            for i in 0..4 {
-                                if (sprite_xw - 5.0
-                                    > engine.sprites.get_sprites(1)[i].screen_region[0])
-                                    && (sprite_x + 5.0
-                                        < engine.sprites.get_sprites(1)[i].screen_region[0]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[2])
-                                    && (sprite_yh
-                                        > engine.sprites.get_sprites(1)[i].screen_region[1])
-                                    && (sprite_y
-                                        < engine.sprites.get_sprites(1)[i].screen_region[1]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[3])
-                                {
-                                    the_move = false;
-                                }
-                            }
-                            if !engine.single_player {
-                                if (sprite_xw - 5.0 > 150.0 && sprite_x + 5.0 < 214.0)
-                                    && sprite_y < 130.0 + 64.0
-                                {
-                                    the_move = false;
-                                }
-
-                                if (sprite_xw - 5.0 > 850.0 && sprite_x + 5.0 < 914.0)
-                                    && sprite_y < 130.0 + 64.0
-                                {
-                                    the_move = false;
-                                }
-                            }
-                            if new_region[0] + new_region[2] < 1030.0 && the_move {
-                                engine.sprites.update_position(new_region, 3);
-                            }
-                        }
-                    }
-                    if engine.input.is_key_released(keyboard.right) {
-                        let new_sheet_pos = [32.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0];
-                        engine.sprites.update_sprite(new_sheet_pos, 3);
                     }
-                    if engine.input.is_key_released(keyboard.left) {
-                        let new_sheet_pos = [32.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0];
-                        engine.sprites.update_sprite(new_sheet_pos, 3);
+                    if engine.quit {
+                        *control_flow = ControlFlow::Exit;
+                        return;
                     }
-                    if engine.input.is_key_down(keyboard.left) {
-                        let new_sheet_pos = [16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0];
-                        engine.sprites.update_sprite(new_sheet_pos, 3);
-                        if engine.single_player {
-                            let mut the_move = true;
-                            let new_region = engine.sprites.get_sprites(3)[0].screen_region;
+                    let alpha = engine.timestep.alpha();
+                    game.render(&mut engine, alpha);
 
-                            let sprite_x = new_region[0];
-                            let sprite_xw = new_region[0] + new_region[2];
-                            let sprite_y = new_region[1];
-                            let sprite_yh = new_region[1] + new_region[3];
-
-                            for i in 0..4 {
-                                if (sprite_xw - 5.0
-                                    > engine.sprites.get_sprites(1)[i].screen_region[0])
-                                    && (sprite_x + 5.0
-                                        < engine.sprites.get_sprites(1)[i].screen_region[0]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[2])
-                                    && (sprite_yh
-                                        > engine.sprites.get_sprites(1)[i].screen_region[1])
-                                    && (sprite_y
-                                        < engine.sprites.get_sprites(1)[i].screen_region[1]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[3])
-                                {
-                                    the_move = false;
-                                }
-                            }
-
-                            if the_move {
-                                let old_sheet_pos = engine.sprites.get_sprites(0)[0].sheet_region;
-                                engine.sprites.update_sprite(
-                                    [
-                                        old_sheet_pos[0] - (p2_speed / 3000.0),
-                                        old_sheet_pos[1],
-                                        0.75,
-                                        old_sheet_pos[3],
-                                    ],
-                                    0,
-                                );
-                                if old_sheet_pos[0] + p2_speed / 3000.0 < -1.0 {
-                                    engine.sprites.update_sprite(
-                                        [1.0, old_sheet_pos[1], 0.75, old_sheet_pos[3]],
-                                        0,
-                                    );
-                                }
-                                for i in 0..4 {
-                                    let current = engine.sprites.get_sprites(1)[i].screen_region;
-                                    let block = engine.sprites.get_sprite_mut(1, i);
-                                    block.screen_region =
-                                        [current[0] + p2_speed, current[1], current[2], current[3]];
-                                }
-                            } else {
-                                engine.leftis_jumping = false;
-                            }
-
-                            let old_sheet_pos = engine.sprites.get_sprites(0)[0].sheet_region;
-                            engine.sprites.update_sprite(
-                                [
-                                    old_sheet_pos[0] - (p2_speed / 3000.0),
-                                    old_sheet_pos[1],
-                                    0.75,
-                                    old_sheet_pos[3],
-                                ],
-                                0,
-                            );
-
-                            for i in 0..4 {
-                                let current = engine.sprites.get_sprites(1)[i].screen_region;
-                                let block = engine.sprites.get_sprite_mut(1, i);
-                                block.screen_region =
-                                    [current[0] + p2_speed, current[1], current[2], current[3]];
-                            }
-                        } else {
-                            //Technically 0 Should always be the background
-                            //2 should always be the sprite until i change it
-                            let old_region = engine.sprites.get_sprites(3)[0].screen_region;
-                            let new_region = [
-                                old_region[0] - p2_speed,
-                                old_region[1],
-                                old_region[2],
-                                old_region[3],
-                            ];
-                            //let new_sheet_pos = [16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0, 16.0 / 64.0];
-
-                            let mut the_move = true;
-
-                            let sprite_x = new_region[0];
-                            let sprite_xw = new_region[0] + new_region[2];
-                            let sprite_y = new_region[1];
-                            let sprite_yh = new_region[1] + new_region[3];
-
-                            for i in 0..4 {
-                                if (sprite_xw - 5.0
-                                    > engine.sprites.get_sprites(1)[i].screen_region[0])
-                                    && (sprite_x + 5.0
-                                        < engine.sprites.get_sprites(1)[i].screen_region[0]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[2])
-                                    && (sprite_yh
-                                        > engine.sprites.get_sprites(1)[i].screen_region[1])
-                                    && (sprite_y
-                                        < engine.sprites.get_sprites(1)[i].screen_region[1]
-                                            + engine.sprites.get_sprites(1)[i].screen_region[3])
-                                {
-                                    the_move = false;
-                                }
-                            }
-
-                            if !engine.single_player {
-                                if (sprite_xw - 5.0 > 150.0 && sprite_x + 5.0 < 214.0)
-                                    && sprite_y < 130.0 + 64.0
-                                {
-                                    the_move = false;
-                                }
-
-                                if (sprite_xw - 5.0 > 850.0 && sprite_x + 5.0 < 914.0)
-                                    && sprite_y < 130.0 + 64.0
-                                {
-                                    the_move = false;
-                                }
-                            }
-
-                            if new_region[0] > -5.0 && the_move {
-                                engine.sprites.update_position(new_region, 3);
-                            }
-                        }
-                    }
-
-                    if engine.sprites.get_sprites(3)[0].screen_region[1] < 85.0 {
-                        let old_region = engine.sprites.get_sprites(3)[0].screen_region;
-                        let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
-                        engine.sprites.update_position(new_region, 3);
-                    }
-
-                    if engine.sprites.get_sprites(2)[0].screen_region[1] < 85.0 {
-                        let old_region = engine.sprites.get_sprites(2)[0].screen_region;
-                        let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
-                        engine.sprites.update_position(new_region, 2);
-                    }
-                    if engine.sprites.get_sprites(3)[0].screen_region[1] < 260.0
-                        && !engine.is_jumping
-                    {
-                        let old_region = engine.sprites.get_sprites(3)[0].screen_region;
-
-                        if engine.single_player {
-                            let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
-                            engine.sprites.update_position(new_region, 3);
-                        } else {
-                            let sprite_x = old_region[0];
-                            let sprite_xw = old_region[0] + old_region[2];
-                            if !(sprite_xw - 5.0 > 150.0 && sprite_x + 5.0 < 214.0)
-                                && !(sprite_xw - 5.0 > 850.0 && sprite_x + 5.0 < 914.0)
-                            {
-                                let old_region = engine.sprites.get_sprites(3)[0].screen_region;
-
-                                let new_region =
-                                    [old_region[0], 85.0, old_region[2], old_region[3]];
-                                engine.sprites.update_position(new_region, 3);
-                            }
-                        }
-                    }
-                    if engine.sprites.get_sprites(2)[0].screen_region[1] < 260.0
-                        && !engine.leftis_jumping
-                    {
-                        let old_region = engine.sprites.get_sprites(2)[0].screen_region;
-
-                        if engine.single_player {
-                            let new_region = [old_region[0], 85.0, old_region[2], old_region[3]];
-                            engine.sprites.update_position(new_region, 2);
-                        } else {
-                            let sprite_x = old_region[0];
-                            let sprite_xw = old_region[0] + old_region[2];
-                            if !(sprite_xw - 5.0 > 150.0 && sprite_x + 5.0 < 214.0)
-                                && !(sprite_xw - 5.0 > 850.0 && sprite_x + 5.0 < 914.0)
-                            {
-                                let old_region = engine.sprites.get_sprites(2)[0].screen_region;
-
-                                let new_region =
-                                    [old_region[0], 85.0, old_region[2], old_region[3]];
-                                engine.sprites.update_position(new_region, 2);
-                            }
-                        }
-                    }
-
-                    // engine.sprites.platform_move();
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        1,
-                        0..(engine.sprites.get_sprites(1).len()),
-                    );
-
-                    //This refreshes the sprite player group to update the position of both sprites
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        2,
-                        0..(engine.sprites.get_sprites(2).len()),
-                    );
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        3,
-                        0..(engine.sprites.get_sprites(3).len()),
-                    );
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        4,
-                        0..(engine.sprites.get_sprites(4).len()),
-                    );
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        5,
-                        0..(engine.sprites.get_sprites(5).len()),
-                    );
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        6,
-                        0..(engine.sprites.get_sprites(6).len()),
-                    );
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        7,
-                        0..(engine.sprites.get_sprites(7).len()),
-                    );
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        8,
-                        0..(engine.sprites.get_sprites(8).len()),
-                    );
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        9,
-                        0..(engine.sprites.get_sprites(9).len()),
-                    );
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        10,
-                        0..(engine.sprites.get_sprites(10).len()),
-                    );
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        11,
-                        0..(engine.sprites.get_sprites(11).len()),
-                    );
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        12,
-                        0..(engine.sprites.get_sprites(12).len()),
-                    );
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        13,
-                        0..(engine.sprites.get_sprites(13).len()),
-                    );
-                    engine.sprites.refresh_sprites(&engine.gpu, 14, 0..1);
-                    engine.sprites.refresh_sprites(&engine.gpu, 15, 0..3);
-                    //Checking if the p1 sprite, collides with group 5, which are only powerups (Speed Up Powerups)
-                    if engine.sprites.check_collisions(2, 5) {
-                        //If so we update their speed (How many pixels they travel across the screen)
-                        p1_speed = 7.0;
-                    }
-
-                    //Check Player2
-                    if engine.sprites.check_collisions(3, 5) {
-                        p2_speed = 7.0
-                    }
-
-                    game.update(&mut engine);
-                    engine.input.next_frame();
-                    if engine.sprites.player_collision() {
-                        if engine.score > 100 {
-                            //Queue Winning Screen
-                            println!("Uhh some player one Shrug");
-                            *control_flow = ControlFlow::Exit;
-                        }
-                        engine.score += 5;
-                        let score_digits = engine.sprites.update_score(engine.score);
-
-                        engine.sprites.update_sprite_score(score_digits[0], 4, 6);
-                        engine.sprites.update_sprite_score(score_digits[1], 4, 7);
-                        engine.sprites.update_sprite_score(score_digits[2], 4, 8);
-                    }
-                    frames += 1;
+                    // Upload whatever sprites the game changed this frame
+                    engine.sprites.flush(&engine.gpu);
 
                     // If the window system is telling us to redraw, let's get our next swapchain image
                     let frame = engine
                         .gpu
                         .surface
+                        .as_ref()
+                        .expect("Engine always runs with a window surface")
                         .get_current_texture()
                         .expect("Failed to acquire next swap chain texture");
                     // And set up a texture view onto it, since the GPU needs a way to interpret those
@@ -926,6 +226,54 @@ impl Engine {
             }
         });
     }
+    // While on, textures loaded through `assets` and shader.wgsl are reloaded when their files
+    // change. Meant for development: it only works from the source tree.
+    pub fn set_hot_reload(&mut self, on: bool) {
+        self.watcher = on.then(Watcher::new);
+    }
+    fn hot_reload(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if !watcher.due() {
+            return;
+        }
+        if watcher.changed(SHADER_PATH.as_ref()) {
+            let reloaded = std::fs::read_to_string(SHADER_PATH)
+                .map_err(|err| err.to_string())
+                .and_then(|source| {
+                    self.sprites
+                        .reload_shader(&self.gpu, &source)
+                        .map_err(|err| err.to_string())
+                });
+            match reloaded {
+                Ok(()) => log::info!("reloaded {}", SHADER_PATH),
+                Err(err) => log::error!("keeping the old shader, {} failed: {}", SHADER_PATH, err),
+            }
+        }
+        for path in self.assets.watched_paths() {
+            if watcher.changed(&path) {
+                match self.assets.reload(&self.gpu, &path) {
+                    Ok(()) => log::info!("reloaded {}", path.to_string_lossy()),
+                    Err(err) => log::error!("couldn't reload {}: {}", path.to_string_lossy(), err),
+                }
+            }
+        }
+    }
+    // How many times a second Game::update runs, 60 by default. Panics unless it's above 0.
+    pub fn set_tick_rate(&mut self, ticks_per_second: f32) {
+        self.timestep.set_tick_rate(ticks_per_second);
+    }
+    // The dt every Game::update gets, in seconds
+    pub fn tick_length(&self) -> f32 {
+        self.timestep.step()
+    }
+    // Ask the event loop to exit once the current frame is finished.
+    pub fn quit(&mut self) {
+        self.quit = true;
+    }
+    // Always decodes and uploads a fresh copy, which hot reload doesn't know about; use
+    // `texture` to share one between sprite groups and have it reload
     pub fn load_texture(
         &self,
         path: impl AsRef<std::path::Path>,
@@ -933,4 +281,16 @@ impl Engine {
     ) -> Result<(wgpu::Texture, image::RgbaImage), image::ImageError> {
         self.gpu.load_texture(path.as_ref(), label)
     }
+    // The cached texture at `path`, only loaded the first time
+    pub fn texture(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Handle<wgpu::Texture>, image::ImageError> {
+        self.assets.texture(&self.gpu, path)
+    }
+    // Packs the builder's images into one texture, which hot reload keeps up to date with the
+    // files they came from
+    pub fn atlas(&mut self, builder: AtlasBuilder) -> Result<Handle<TextureAtlas>, AtlasError> {
+        self.assets.atlas(&self.gpu, builder)
+    }
 }
@@ -0,0 +1,365 @@
+use std::sync::{Arc, Mutex};
+
+// Buttons by where they are on the pad rather than what they're labelled, so South is A on an
+// Xbox pad and Cross on a PlayStation one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub(crate) const COUNT: usize = 14;
+}
+
+// Sticks go from -1 to 1 with up and right positive; triggers from 0 (let go) to 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub(crate) const COUNT: usize = 6;
+
+    // The other half of this axis's stick, if it's a stick
+    fn partner(self) -> Option<GamepadAxis> {
+        match self {
+            GamepadAxis::LeftStickX => Some(GamepadAxis::LeftStickY),
+            GamepadAxis::LeftStickY => Some(GamepadAxis::LeftStickX),
+            GamepadAxis::RightStickX => Some(GamepadAxis::RightStickY),
+            GamepadAxis::RightStickY => Some(GamepadAxis::RightStickX),
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => None,
+        }
+    }
+}
+
+// Whichever id the backend gives each pad. It stays the same until the pad disconnects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    Button {
+        id: GamepadId,
+        button: GamepadButton,
+        down: bool,
+    },
+    Axis {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+// Where gamepad events come from. The engine polls it once a frame; GilrsGamepads reads real
+// controllers and FakeGamepads stands in for them in tests.
+pub trait GamepadBackend: Send {
+    // Everything that happened since the last poll, oldest first
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>);
+}
+
+// A backend that only reports what it's told to, for tests and demos. Clones share one event
+// queue, so keep a clone to drive the one handed to Input.
+#[derive(Clone, Default)]
+pub struct FakeGamepads {
+    queue: Arc<Mutex<Vec<GamepadEvent>>>,
+}
+
+impl FakeGamepads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn send(&self, event: GamepadEvent) {
+        self.queue
+            .lock()
+            .expect("nothing panics holding the queue")
+            .push(event);
+    }
+    pub fn connect(&self, id: GamepadId) {
+        self.send(GamepadEvent::Connected(id));
+    }
+    pub fn disconnect(&self, id: GamepadId) {
+        self.send(GamepadEvent::Disconnected(id));
+    }
+    pub fn press(&self, id: GamepadId, button: GamepadButton) {
+        self.send(GamepadEvent::Button {
+            id,
+            button,
+            down: true,
+        });
+    }
+    pub fn release(&self, id: GamepadId, button: GamepadButton) {
+        self.send(GamepadEvent::Button {
+            id,
+            button,
+            down: false,
+        });
+    }
+    pub fn move_axis(&self, id: GamepadId, axis: GamepadAxis, value: f32) {
+        self.send(GamepadEvent::Axis { id, axis, value });
+    }
+}
+
+impl GamepadBackend for FakeGamepads {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        events.append(&mut self.queue.lock().expect("nothing panics holding the queue"));
+    }
+}
+
+// Real controllers, through gilrs. Engine::run installs one, so pads work without any setup;
+// pads that were already plugged in show up as connected on the first poll.
+pub struct GilrsGamepads {
+    gilrs: gilrs::Gilrs,
+    already_connected: Vec<GamepadId>,
+}
+
+impl GilrsGamepads {
+    // None (with a warning logged) where gilrs can't read controllers
+    pub fn new() -> Option<Self> {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(err) => {
+                log::warn!("No gamepads: {}", err);
+                return None;
+            }
+        };
+        let already_connected = gilrs.gamepads().map(|(id, _)| gilrs_id(id)).collect();
+        Some(Self {
+            gilrs,
+            already_connected,
+        })
+    }
+}
+
+impl GamepadBackend for GilrsGamepads {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        events.extend(
+            self.already_connected
+                .drain(..)
+                .map(GamepadEvent::Connected),
+        );
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let id = gilrs_id(id);
+            let event = match event {
+                gilrs::EventType::Connected => GamepadEvent::Connected(id),
+                gilrs::EventType::Disconnected => GamepadEvent::Disconnected(id),
+                gilrs::EventType::ButtonPressed(button, _) => match gilrs_button(button) {
+                    Some(button) => GamepadEvent::Button {
+                        id,
+                        button,
+                        down: true,
+                    },
+                    None => continue,
+                },
+                gilrs::EventType::ButtonReleased(button, _) => match gilrs_button(button) {
+                    Some(button) => GamepadEvent::Button {
+                        id,
+                        button,
+                        down: false,
+                    },
+                    None => continue,
+                },
+                // gilrs counts the triggers as analog buttons
+                gilrs::EventType::ButtonChanged(button, value, _) => match gilrs_trigger(button) {
+                    Some(axis) => GamepadEvent::Axis { id, axis, value },
+                    None => continue,
+                },
+                gilrs::EventType::AxisChanged(axis, value, _) => match gilrs_axis(axis) {
+                    Some(axis) => GamepadEvent::Axis { id, axis, value },
+                    None => continue,
+                },
+                _ => continue,
+            };
+            events.push(event);
+        }
+    }
+}
+
+fn gilrs_id(id: gilrs::GamepadId) -> GamepadId {
+    GamepadId(usize::from(id) as u32)
+}
+
+fn gilrs_button(button: gilrs::Button) -> Option<GamepadButton> {
+    use gilrs::Button as G;
+    Some(match button {
+        G::South => GamepadButton::South,
+        G::East => GamepadButton::East,
+        G::West => GamepadButton::West,
+        G::North => GamepadButton::North,
+        // gilrs calls the bumpers triggers and the triggers Trigger2
+        G::LeftTrigger => GamepadButton::LeftBumper,
+        G::RightTrigger => GamepadButton::RightBumper,
+        G::Select => GamepadButton::Select,
+        G::Start => GamepadButton::Start,
+        G::LeftThumb => GamepadButton::LeftStick,
+        G::RightThumb => GamepadButton::RightStick,
+        G::DPadUp => GamepadButton::DPadUp,
+        G::DPadDown => GamepadButton::DPadDown,
+        G::DPadLeft => GamepadButton::DPadLeft,
+        G::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn gilrs_trigger(button: gilrs::Button) -> Option<GamepadAxis> {
+    match button {
+        gilrs::Button::LeftTrigger2 => Some(GamepadAxis::LeftTrigger),
+        gilrs::Button::RightTrigger2 => Some(GamepadAxis::RightTrigger),
+        _ => None,
+    }
+}
+
+fn gilrs_axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+    // gilrs already has up as positive
+    match axis {
+        gilrs::Axis::LeftStickX => Some(GamepadAxis::LeftStickX),
+        gilrs::Axis::LeftStickY => Some(GamepadAxis::LeftStickY),
+        gilrs::Axis::RightStickX => Some(GamepadAxis::RightStickX),
+        gilrs::Axis::RightStickY => Some(GamepadAxis::RightStickY),
+        _ => None,
+    }
+}
+
+// One connected pad, tracked the same way Input tracks keys
+pub(crate) struct Gamepad {
+    pub(crate) id: GamepadId,
+    now_buttons: [bool; GamepadButton::COUNT],
+    prev_buttons: [bool; GamepadButton::COUNT],
+    // Raw values, before the deadzone
+    now_axes: [f32; GamepadAxis::COUNT],
+    prev_axes: [f32; GamepadAxis::COUNT],
+}
+
+impl Gamepad {
+    pub(crate) fn new(id: GamepadId) -> Self {
+        Self {
+            id,
+            now_buttons: [false; GamepadButton::COUNT],
+            prev_buttons: [false; GamepadButton::COUNT],
+            now_axes: [0.0; GamepadAxis::COUNT],
+            prev_axes: [0.0; GamepadAxis::COUNT],
+        }
+    }
+
+    pub(crate) fn set_button(&mut self, button: GamepadButton, down: bool) {
+        self.now_buttons[button as usize] = down;
+    }
+    pub(crate) fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.now_axes[axis as usize] = value.clamp(-1.0, 1.0);
+    }
+    pub(crate) fn next_frame(&mut self) {
+        self.prev_buttons = self.now_buttons;
+        self.prev_axes = self.now_axes;
+    }
+
+    pub(crate) fn button(&self, button: GamepadButton) -> bool {
+        self.now_buttons[button as usize]
+    }
+    pub(crate) fn prev_button(&self, button: GamepadButton) -> bool {
+        self.prev_buttons[button as usize]
+    }
+    pub(crate) fn axis(&self, axis: GamepadAxis, deadzone: f32) -> f32 {
+        with_deadzone(&self.now_axes, axis, deadzone)
+    }
+    pub(crate) fn prev_axis(&self, axis: GamepadAxis, deadzone: f32) -> f32 {
+        with_deadzone(&self.prev_axes, axis, deadzone)
+    }
+}
+
+// Sticks never rest exactly at 0, so anything within `deadzone` of the centre counts as 0 and
+// the rest is stretched back out to reach 1. Sticks measure the distance with both halves at
+// once, so pushing diagonally doesn't hit the deadzone on one half only.
+fn with_deadzone(axes: &[f32; GamepadAxis::COUNT], axis: GamepadAxis, deadzone: f32) -> f32 {
+    let value = axes[axis as usize];
+    let distance = match axis.partner() {
+        Some(partner) => value.hypot(axes[partner as usize]),
+        None => value.abs(),
+    };
+    if distance <= deadzone {
+        return 0.0;
+    }
+    let scale = ((distance - deadzone) / (1.0 - deadzone)).min(1.0) / distance;
+    value * scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_button_has_a_gilrs_button() {
+        use gilrs::Button as G;
+        let mapped: Vec<_> = [
+            G::South,
+            G::East,
+            G::North,
+            G::West,
+            G::C,
+            G::Z,
+            G::LeftTrigger,
+            G::LeftTrigger2,
+            G::RightTrigger,
+            G::RightTrigger2,
+            G::Select,
+            G::Start,
+            G::Mode,
+            G::LeftThumb,
+            G::RightThumb,
+            G::DPadUp,
+            G::DPadDown,
+            G::DPadLeft,
+            G::DPadRight,
+        ]
+        .into_iter()
+        .filter_map(gilrs_button)
+        .collect();
+        assert_eq!(mapped.len(), GamepadButton::COUNT);
+        for (i, button) in mapped.iter().enumerate() {
+            assert!(!mapped[..i].contains(button), "{:?} twice", button);
+        }
+        assert_eq!(
+            gilrs_button(G::LeftTrigger),
+            Some(GamepadButton::LeftBumper)
+        );
+    }
+
+    #[test]
+    fn triggers_are_axes() {
+        assert_eq!(
+            gilrs_trigger(gilrs::Button::LeftTrigger2),
+            Some(GamepadAxis::LeftTrigger)
+        );
+        assert_eq!(
+            gilrs_trigger(gilrs::Button::RightTrigger2),
+            Some(GamepadAxis::RightTrigger)
+        );
+        assert_eq!(gilrs_trigger(gilrs::Button::South), None);
+        assert_eq!(gilrs_button(gilrs::Button::LeftTrigger2), None);
+        assert_eq!(
+            gilrs_axis(gilrs::Axis::RightStickY),
+            Some(GamepadAxis::RightStickY)
+        );
+        assert_eq!(gilrs_axis(gilrs::Axis::DPadX), None);
+    }
+}
@@ -2,7 +2,8 @@
 use winit::window::Window;
 pub struct WGPU {
     //instance: wgpu::Instance,
-    pub(crate) surface: wgpu::Surface,
+    // None for a headless WGPU, which can't present anything
+    pub(crate) surface: Option<wgpu::Surface>,
     //adapter: wgpu::Adapter,
     pub(crate) device: wgpu::Device,
     pub(crate) queue: wgpu::Queue,
@@ -16,6 +17,12 @@ impl WGPU {
     ) -> Result<(wgpu::Texture, image::RgbaImage), image::ImageError> {
         // This ? operator will return the error if there is one, unwrapping the result otherwise.
         let img = image::open(path)?.to_rgba8();
+        let texture = self.create_texture(&img, label);
+        Ok((texture, img))
+    }
+
+    // Uploads an image that's already in memory
+    pub fn create_texture(&self, img: &image::RgbaImage, label: Option<&str>) -> wgpu::Texture {
         let (width, height) = img.dimensions();
         let size = wgpu::Extent3d {
             width,
@@ -32,17 +39,45 @@ impl WGPU {
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
+        self.write_texture(&texture, img);
+        texture
+    }
+
+    // Replaces a texture's pixels. `img` has to be the same size as the texture.
+    pub fn write_texture(&self, texture: &wgpu::Texture, img: &image::RgbaImage) {
+        self.write_texture_at(texture, [0, 0], img);
+    }
+    // Replaces just the part of a texture with its top left at `origin`
+    pub fn write_texture_at(
+        &self,
+        texture: &wgpu::Texture,
+        origin: [u32; 2],
+        img: &image::RgbaImage,
+    ) {
+        let (width, height) = img.dimensions();
         self.queue.write_texture(
-            texture.as_image_copy(),
-            &img,
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            img,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * width),
                 rows_per_image: Some(height),
             },
-            size,
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
         );
-        Ok((texture, img))
     }
 
     pub(crate) async fn new(window: &Window) -> Self {
@@ -117,16 +152,74 @@ impl WGPU {
 
         Self {
             //instance,
-            surface,
+            surface: Some(surface),
             //adapter,
             device,
             queue,
             config,
         }
     }
+    // A WGPU with no window or surface, e.g. for tests or offscreen work. Prefers a software
+    // (fallback) adapter so it works on machines without a GPU; returns None if there's no
+    // adapter at all.
+    pub async fn headless(format: wgpu::TextureFormat) -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let mut options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        };
+        let adapter = match instance.request_adapter(&options).await {
+            Some(adapter) => adapter,
+            None => {
+                options.force_fallback_adapter = false;
+                instance.request_adapter(&options).await?
+            }
+        };
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                },
+                None,
+            )
+            .await
+            .ok()?;
+        // Nothing is ever presented, but SpriteRender still needs to know what format it draws to
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: 1,
+            height: 1,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        Some(Self {
+            surface: None,
+            device,
+            queue,
+            config,
+        })
+    }
     pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
         self.config.width = size.width;
         self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+}
+
+// A headless WGPU for tests, or None (after saying so) if this machine has no adapter at all,
+// in which case the test should return early and pass trivially
+#[cfg(test)]
+pub(crate) fn test_gpu() -> Option<WGPU> {
+    let gpu = pollster::block_on(WGPU::headless(wgpu::TextureFormat::Rgba8UnormSrgb));
+    if gpu.is_none() {
+        eprintln!("no wgpu adapter available, skipping");
     }
+    gpu
 }
@@ -1,6 +1,12 @@
+use crate::gamepad::Gamepad;
+use crate::{
+    ActionMap, AxisDirection, Binding, GPUCamera, GamepadAxis, GamepadBackend, GamepadButton,
+    GamepadEvent, GamepadId,
+};
 pub use winit::dpi::PhysicalPosition as MousePos;
+use winit::dpi::PhysicalSize;
 pub use winit::event::VirtualKeyCode as Key;
-use winit::event::{ElementState, MouseButton};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
 
 pub struct Input {
     now_keys: Box<[bool]>,
@@ -9,6 +15,106 @@ pub struct Input {
     prev_mouse: Box<[bool]>,
     now_mouse_pos: MousePos<f64>,
     prev_mouse_pos: MousePos<f64>,
+    // Lines scrolled since the last next_frame
+    wheel: [f32; 2],
+    now_cursor_inside: bool,
+    prev_cursor_inside: bool,
+    // Inner size of the window in physical pixels, for turning the cursor into world coordinates
+    window_size: [f32; 2],
+    // Everything that went down since the last next_frame, in order
+    pressed: Vec<Binding>,
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    // Connected pads, oldest first
+    gamepads: Vec<Gamepad>,
+    // players[0] is player one. There's always at least one.
+    players: Vec<Player>,
+    connected: Vec<GamepadId>,
+    disconnected: Vec<GamepadId>,
+    deadzone: f32,
+}
+
+// Touchpads scroll in pixels rather than lines; this many make a line
+const PIXELS_PER_LINE: f32 = 20.0;
+
+// How far an analog binding has to go before it counts as down
+const ANALOG_PRESS_POINT: f32 = 0.5;
+
+// A local player's bindings and the devices they're using: a pad, if any, and the keyboard and
+// mouse unless they've been taken off them
+struct Player {
+    actions: ActionMap,
+    gamepad: Option<GamepadId>,
+    keyboard: bool,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            actions: ActionMap::default(),
+            gamepad: None,
+            keyboard: true,
+        }
+    }
+}
+
+// Input as one player sees it: only their bindings, and only their pad. Everyone shares the
+// keyboard and mouse unless set_player_keyboard takes a player off them, so players on one
+// keyboard need different keys.
+pub struct PlayerInput<'a> {
+    input: &'a Input,
+    player: usize,
+}
+
+impl<'a> PlayerInput<'a> {
+    // None for players past the last one set up
+    pub fn actions(&self) -> Option<&'a ActionMap> {
+        self.input.players.get(self.player).map(|p| &p.actions)
+    }
+    fn bindings(&self, action: &str) -> &'a [Binding] {
+        self.actions()
+            .map_or(&[], |actions| actions.bindings(action))
+    }
+    pub fn gamepad(&self) -> Option<GamepadId> {
+        self.input.player_gamepad(self.player)
+    }
+
+    // Whether anything bound to `action` is held. Unbound actions are never down.
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|&b| self.input.is_binding_down_for(b, Some(self.player)))
+    }
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.bindings(action).iter().any(|&b| {
+            self.input.is_binding_down_for(b, Some(self.player))
+                && !self.input.was_binding_down_for(b, Some(self.player))
+        })
+    }
+    // Only once the last of its bindings is let go
+    pub fn is_action_released(&self, action: &str) -> bool {
+        let was = self
+            .bindings(action)
+            .iter()
+            .any(|&b| self.input.was_binding_down_for(b, Some(self.player)));
+        was && !self.is_action_down(action)
+    }
+    // From -1 to 1, like key_axis but with sticks in between. Holding both directions cancels
+    // out.
+    pub fn action_axis(&self, axis: &str) -> f32 {
+        let Some(bindings) = self
+            .actions()
+            .and_then(|actions| actions.axis_bindings(axis))
+        else {
+            return 0.0;
+        };
+        let pushed = |bindings: &[Binding]| {
+            bindings
+                .iter()
+                .map(|&b| self.input.binding_value_at(b, false, Some(self.player)))
+                .fold(0.0, f32::max)
+        };
+        pushed(&bindings.positive) - pushed(&bindings.negative)
+    }
 }
 impl Default for Input {
     fn default() -> Self {
@@ -19,6 +125,17 @@ impl Default for Input {
             prev_mouse: vec![false; 16].into_boxed_slice(),
             now_mouse_pos: MousePos { x: 0.0, y: 0.0 },
             prev_mouse_pos: MousePos { x: 0.0, y: 0.0 },
+            wheel: [0.0, 0.0],
+            now_cursor_inside: false,
+            prev_cursor_inside: false,
+            window_size: [1.0, 1.0],
+            pressed: Vec::new(),
+            gamepad_backend: None,
+            gamepads: Vec::new(),
+            players: vec![Player::default()],
+            connected: Vec::new(),
+            disconnected: Vec::new(),
+            deadzone: 0.2,
         }
     }
 }
@@ -37,26 +154,31 @@ impl Input {
         !self.now_keys[kc as usize] && self.prev_keys[kc as usize]
     }
     pub fn is_mouse_down(&self, button: MouseButton) -> bool {
-        self.now_mouse[Self::mouse_button_to_usize(button)]
+        Self::mouse_button(&self.now_mouse, button)
     }
+    // Extra buttons come after the named ones. Ones past the end of the table are ignored.
     fn mouse_button_to_usize(button: MouseButton) -> usize {
         match button {
             MouseButton::Left => 0,
             MouseButton::Right => 1,
             MouseButton::Middle => 2,
-            MouseButton::Other(n) => n as usize,
+            MouseButton::Other(n) => 3 + n as usize,
         }
     }
+    fn mouse_button(buttons: &[bool], button: MouseButton) -> bool {
+        buttons
+            .get(Self::mouse_button_to_usize(button))
+            .copied()
+            .unwrap_or(false)
+    }
     pub fn is_mouse_up(&self, mb: MouseButton) -> bool {
-        !self.now_mouse[Self::mouse_button_to_usize(mb)]
+        !Self::mouse_button(&self.now_mouse, mb)
     }
     pub fn is_mouse_pressed(&self, mb: MouseButton) -> bool {
-        self.now_mouse[Self::mouse_button_to_usize(mb)]
-            && !self.prev_mouse[Self::mouse_button_to_usize(mb)]
+        Self::mouse_button(&self.now_mouse, mb) && !Self::mouse_button(&self.prev_mouse, mb)
     }
     pub fn is_mouse_released(&self, mb: MouseButton) -> bool {
-        !self.now_mouse[Self::mouse_button_to_usize(mb)]
-            && self.prev_mouse[Self::mouse_button_to_usize(mb)]
+        !Self::mouse_button(&self.now_mouse, mb) && Self::mouse_button(&self.prev_mouse, mb)
     }
     pub fn mouse_pos(&self) -> MousePos<f64> {
         self.now_mouse_pos
@@ -67,14 +189,293 @@ impl Input {
             y: self.now_mouse_pos.y - self.prev_mouse_pos.y,
         }
     }
+    // Where the cursor is in the world `camera` shows, taking the window's pixels (y down from
+    // the top left) to the camera's coordinates (y up from the bottom left)
+    pub fn mouse_world_pos(&self, camera: &GPUCamera) -> [f32; 2] {
+        self.window_to_world(self.now_mouse_pos, camera)
+    }
+    pub fn mouse_world_delta(&self, camera: &GPUCamera) -> [f32; 2] {
+        let now = self.window_to_world(self.now_mouse_pos, camera);
+        let prev = self.window_to_world(self.prev_mouse_pos, camera);
+        [now[0] - prev[0], now[1] - prev[1]]
+    }
+    fn window_to_world(&self, pos: MousePos<f64>, camera: &GPUCamera) -> [f32; 2] {
+        let [w, h] = self.window_size;
+        [
+            camera.screen_pos[0] + pos.x as f32 / w * camera.screen_size[0],
+            camera.screen_pos[1] + (1.0 - pos.y as f32 / h) * camera.screen_size[1],
+        ]
+    }
+    // Lines scrolled this frame: y is positive away from the player, x to the right
+    pub fn mouse_wheel(&self) -> [f32; 2] {
+        self.wheel
+    }
+    pub fn is_cursor_in_window(&self) -> bool {
+        self.now_cursor_inside
+    }
+    pub fn cursor_entered(&self) -> bool {
+        self.now_cursor_inside && !self.prev_cursor_inside
+    }
+    pub fn cursor_left(&self) -> bool {
+        !self.now_cursor_inside && self.prev_cursor_inside
+    }
     pub fn key_axis(&self, down: Key, up: Key) -> f32 {
         (if self.is_key_down(down) { -1.0 } else { 0.0 })
             + (if self.is_key_down(up) { 1.0 } else { 0.0 })
     }
+
+    // How far the binding is pushed, from 0 to 1. Keys and buttons are all or nothing; gamepad
+    // bindings go by whichever connected pad is pushing hardest.
+    pub fn binding_value(&self, binding: Binding) -> f32 {
+        self.binding_value_at(binding, false, None)
+    }
+    // `player` limits gamepad bindings to that player's pad, and keyboard and mouse ones to
+    // players on the keyboard; None takes any device
+    fn binding_value_at(&self, binding: Binding, prev: bool, player: Option<usize>) -> f32 {
+        let on = |down: bool| if down { 1.0 } else { 0.0 };
+        let keyboard = player.is_none_or(|player| self.player_keyboard(player));
+        let pads = self.gamepads.iter().filter(|pad| match player {
+            Some(player) => self.player_gamepad(player) == Some(pad.id),
+            None => true,
+        });
+        match binding {
+            Binding::Key(_) | Binding::Mouse(_) if !keyboard => 0.0,
+            Binding::Key(key) => on(if prev {
+                self.prev_keys[key as usize]
+            } else {
+                self.now_keys[key as usize]
+            }),
+            Binding::Mouse(button) => on(if prev {
+                Self::mouse_button(&self.prev_mouse, button)
+            } else {
+                Self::mouse_button(&self.now_mouse, button)
+            }),
+            Binding::GamepadButton(button) => on(pads.into_iter().any(|pad| {
+                if prev {
+                    pad.prev_button(button)
+                } else {
+                    pad.button(button)
+                }
+            })),
+            Binding::GamepadAxis(axis, direction) => {
+                let sign = match direction {
+                    AxisDirection::Negative => -1.0,
+                    AxisDirection::Positive => 1.0,
+                };
+                pads.map(|pad| {
+                    let value = if prev {
+                        pad.prev_axis(axis, self.deadzone)
+                    } else {
+                        pad.axis(axis, self.deadzone)
+                    };
+                    (value * sign).max(0.0)
+                })
+                .fold(0.0, f32::max)
+            }
+        }
+    }
+    fn is_binding_down_for(&self, binding: Binding, player: Option<usize>) -> bool {
+        self.binding_value_at(binding, false, player) >= ANALOG_PRESS_POINT
+    }
+    fn was_binding_down_for(&self, binding: Binding, player: Option<usize>) -> bool {
+        self.binding_value_at(binding, true, player) >= ANALOG_PRESS_POINT
+    }
+    pub fn is_binding_down(&self, binding: Binding) -> bool {
+        self.is_binding_down_for(binding, None)
+    }
+    pub fn is_binding_pressed(&self, binding: Binding) -> bool {
+        self.is_binding_down_for(binding, None) && !self.was_binding_down_for(binding, None)
+    }
+    pub fn is_binding_released(&self, binding: Binding) -> bool {
+        !self.is_binding_down_for(binding, None) && self.was_binding_down_for(binding, None)
+    }
+    // What went down this frame, for "press a key to bind it" screens
+    pub fn pressed_bindings(&self) -> &[Binding] {
+        &self.pressed
+    }
+
+    // One player's controls and pad. Players past the last one set up have no bindings, so
+    // nothing is ever down for them.
+    pub fn player(&self, player: usize) -> PlayerInput<'_> {
+        PlayerInput {
+            input: self,
+            player,
+        }
+    }
+    pub fn player_count(&self) -> usize {
+        self.players.len()
+    }
+    pub fn set_player_actions(&mut self, player: usize, actions: ActionMap) {
+        *self.player_actions_mut(player) = actions;
+    }
+    pub fn player_actions_mut(&mut self, player: usize) -> &mut ActionMap {
+        &mut self.player_slot(player).actions
+    }
+    fn player_slot(&mut self, player: usize) -> &mut Player {
+        if self.players.len() <= player {
+            self.players.resize_with(player + 1, Player::default);
+        }
+        &mut self.players[player]
+    }
+
+    // Player one's controls, which is all a single player game needs
+    pub fn actions(&self) -> &ActionMap {
+        &self.players[0].actions
+    }
+    pub fn actions_mut(&mut self) -> &mut ActionMap {
+        self.player_actions_mut(0)
+    }
+    pub fn set_actions(&mut self, actions: ActionMap) {
+        self.set_player_actions(0, actions);
+    }
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.player(0).is_action_down(action)
+    }
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.player(0).is_action_pressed(action)
+    }
+    pub fn is_action_released(&self, action: &str) -> bool {
+        self.player(0).is_action_released(action)
+    }
+    pub fn action_axis(&self, axis: &str) -> f32 {
+        self.player(0).action_axis(axis)
+    }
+
+    // Where gamepad events come from, replacing the GilrsGamepads Engine::run starts with.
+    // Without one, gamepads only show up through handle_gamepad_event.
+    pub fn set_gamepad_backend(&mut self, backend: impl GamepadBackend + 'static) {
+        self.gamepad_backend = Some(Box::new(backend));
+    }
+    // Takes in everything the backend has seen since the last call; the engine does this once
+    // a frame
+    pub fn poll_gamepads(&mut self) {
+        let Some(backend) = &mut self.gamepad_backend else {
+            return;
+        };
+        let mut events = Vec::new();
+        backend.poll(&mut events);
+        for event in events {
+            self.handle_gamepad_event(event);
+        }
+    }
+    pub fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(id) => {
+                if self.gamepad(id).is_none() {
+                    self.gamepads.push(Gamepad::new(id));
+                    self.connected.push(id);
+                    // To the first player without one
+                    match self.players.iter_mut().find(|p| p.gamepad.is_none()) {
+                        Some(player) => player.gamepad = Some(id),
+                        None => self.players.push(Player {
+                            gamepad: Some(id),
+                            ..Player::default()
+                        }),
+                    }
+                }
+            }
+            GamepadEvent::Disconnected(id) => {
+                if self.gamepad(id).is_some() {
+                    self.gamepads.retain(|pad| pad.id != id);
+                    self.disconnected.push(id);
+                    for player in &mut self.players {
+                        if player.gamepad == Some(id) {
+                            player.gamepad = None;
+                        }
+                    }
+                }
+            }
+            GamepadEvent::Button { id, button, down } => {
+                if let Some(pad) = self.gamepads.iter_mut().find(|pad| pad.id == id) {
+                    if down && !pad.button(button) {
+                        self.pressed.push(Binding::GamepadButton(button));
+                    }
+                    pad.set_button(button, down);
+                }
+            }
+            GamepadEvent::Axis { id, axis, value } => {
+                if let Some(pad) = self.gamepads.iter_mut().find(|pad| pad.id == id) {
+                    pad.set_axis(axis, value);
+                }
+            }
+        }
+    }
+
+    fn gamepad(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.gamepads.iter().find(|pad| pad.id == id)
+    }
+    // Every connected pad, oldest first
+    pub fn gamepads(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepads.iter().map(|pad| pad.id)
+    }
+    pub fn gamepads_connected(&self) -> &[GamepadId] {
+        &self.connected
+    }
+    pub fn gamepads_disconnected(&self) -> &[GamepadId] {
+        &self.disconnected
+    }
+    // Pads that aren't connected have nothing down
+    pub fn is_gamepad_button_down(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad(id).is_some_and(|pad| pad.button(button))
+    }
+    pub fn is_gamepad_button_pressed(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad(id)
+            .is_some_and(|pad| pad.button(button) && !pad.prev_button(button))
+    }
+    pub fn is_gamepad_button_released(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepad(id)
+            .is_some_and(|pad| !pad.button(button) && pad.prev_button(button))
+    }
+    // With the deadzone taken out
+    pub fn gamepad_axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        self.gamepad(id)
+            .map_or(0.0, |pad| pad.axis(axis, self.deadzone))
+    }
+    // How far from the centre (0 to just under 1) sticks and triggers have to move to count;
+    // 0.2 to start with
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    // Pads are handed to players in the order they connect. A player whose pad disconnects
+    // keeps their slot, and the next pad to connect fills it.
+    pub fn player_gamepad(&self, player: usize) -> Option<GamepadId> {
+        self.players.get(player).and_then(|p| p.gamepad)
+    }
+    // Whether `player`'s keyboard and mouse bindings count, true to start with. Taking a player
+    // with a pad off the keyboard leaves it to the others.
+    pub fn set_player_keyboard(&mut self, player: usize, keyboard: bool) {
+        self.player_slot(player).keyboard = keyboard;
+    }
+    pub fn player_keyboard(&self, player: usize) -> bool {
+        self.players.get(player).is_none_or(|p| p.keyboard)
+    }
+    // Gives `player` the pad `id`, swapping with whoever had it
+    pub fn assign_gamepad(&mut self, player: usize, id: GamepadId) {
+        let previous = self.player_slot(player).gamepad;
+        for other in &mut self.players {
+            if other.gamepad == Some(id) {
+                other.gamepad = previous;
+            }
+        }
+        self.players[player].gamepad = Some(id);
+    }
+
     pub fn next_frame(&mut self) {
+        self.pressed.clear();
+        self.connected.clear();
+        self.disconnected.clear();
+        for pad in &mut self.gamepads {
+            pad.next_frame();
+        }
         self.prev_keys.copy_from_slice(&self.now_keys);
         self.prev_mouse.copy_from_slice(&self.now_mouse);
         self.prev_mouse_pos = self.now_mouse_pos;
+        self.prev_cursor_inside = self.now_cursor_inside;
+        self.wheel = [0.0, 0.0];
     }
     pub fn handle_key_event(&mut self, ke: winit::event::KeyboardInput) {
         if let winit::event::KeyboardInput {
@@ -83,28 +484,327 @@ impl Input {
             ..
         } = ke
         {
-            match state {
-                winit::event::ElementState::Pressed => {
-                    self.now_keys[keycode as usize] = true;
-                }
-                winit::event::ElementState::Released => {
-                    self.now_keys[keycode as usize] = false;
-                }
-            }
+            self.set_key(keycode, state == ElementState::Pressed);
         }
     }
+    fn set_key(&mut self, key: Key, down: bool) {
+        if down && !self.now_keys[key as usize] {
+            self.pressed.push(Binding::Key(key));
+        }
+        self.now_keys[key as usize] = down;
+    }
     pub fn handle_mouse_button(&mut self, state: ElementState, button: MouseButton) {
-        let button = Self::mouse_button_to_usize(button);
+        let index = Self::mouse_button_to_usize(button);
+        if index >= self.now_mouse.len() {
+            return;
+        }
         match state {
             ElementState::Pressed => {
-                self.now_mouse[button] = true;
+                if !self.now_mouse[index] {
+                    self.pressed.push(Binding::Mouse(button));
+                }
+                self.now_mouse[index] = true;
             }
             ElementState::Released => {
-                self.now_mouse[button] = false;
+                self.now_mouse[index] = false;
             }
         }
     }
     pub fn handle_mouse_move(&mut self, position: MousePos<f64>) {
         self.now_mouse_pos = position;
+        // Some platforms never send an enter event for a cursor that starts inside the window
+        self.now_cursor_inside = true;
+    }
+    pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        let [x, y] = match delta {
+            MouseScrollDelta::LineDelta(x, y) => [x, y],
+            MouseScrollDelta::PixelDelta(pos) => [
+                pos.x as f32 / PIXELS_PER_LINE,
+                pos.y as f32 / PIXELS_PER_LINE,
+            ],
+        };
+        self.wheel[0] += x;
+        self.wheel[1] += y;
+    }
+    // The cursor keeps its last position after it leaves
+    pub fn handle_cursor_inside(&mut self, inside: bool) {
+        self.now_cursor_inside = inside;
+    }
+    pub fn handle_resize(&mut self, size: PhysicalSize<u32>) {
+        self.window_size = [size.width.max(1) as f32, size.height.max(1) as f32];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AxisBinding, FakeGamepads};
+
+    fn controls() -> Input {
+        let mut input = Input::default();
+        let mut actions = ActionMap::new();
+        actions.bind("jump", Key::Up).bind("jump", Key::Space);
+        actions.bind_axis("move_x", Key::Left, Key::Right);
+        input.set_actions(actions);
+        input
+    }
+
+    #[test]
+    fn any_binding_triggers_an_action() {
+        let mut input = controls();
+        input.set_key(Key::Space, true);
+        assert!(input.is_action_down("jump"));
+        assert!(input.is_action_pressed("jump"));
+        assert_eq!(input.pressed_bindings(), &[Binding::Key(Key::Space)]);
+        input.next_frame();
+        input.set_key(Key::Up, true);
+        input.set_key(Key::Space, false);
+        // Still held through the other key
+        assert!(!input.is_action_released("jump"));
+        assert!(input.is_action_pressed("jump"));
+        assert!(!input.is_action_down("walk"));
+    }
+
+    #[test]
+    fn axes_cancel_out() {
+        let mut input = controls();
+        input.set_key(Key::Left, true);
+        assert_eq!(input.action_axis("move_x"), -1.0);
+        input.set_key(Key::Right, true);
+        assert_eq!(input.action_axis("move_x"), 0.0);
+        input.set_key(Key::Left, false);
+        assert_eq!(input.action_axis("move_x"), 1.0);
+    }
+
+    #[test]
+    fn rebinding_takes_effect_immediately() {
+        let mut input = controls();
+        input
+            .actions_mut()
+            .rebind("jump", [Binding::Mouse(MouseButton::Left)]);
+        input.set_key(Key::Space, true);
+        assert!(!input.is_action_down("jump"));
+        input.handle_mouse_button(ElementState::Pressed, MouseButton::Left);
+        assert!(input.is_action_down("jump"));
+    }
+
+    fn with_fake_pads() -> (Input, FakeGamepads) {
+        let pads = FakeGamepads::new();
+        let mut input = controls();
+        input.set_gamepad_backend(pads.clone());
+        let mut actions = input.actions().clone();
+        actions.bind("jump", GamepadButton::South);
+        actions.rebind_axis(
+            "move_x",
+            AxisBinding {
+                negative: vec![
+                    Key::Left.into(),
+                    Binding::GamepadAxis(GamepadAxis::LeftStickX, AxisDirection::Negative),
+                ],
+                positive: vec![
+                    Key::Right.into(),
+                    Binding::GamepadAxis(GamepadAxis::LeftStickX, AxisDirection::Positive),
+                ],
+            },
+        );
+        input.set_actions(actions);
+        (input, pads)
+    }
+
+    #[test]
+    fn gamepad_buttons_press_and_release_like_keys() {
+        let (mut input, pads) = with_fake_pads();
+        let pad = GamepadId(7);
+        pads.connect(pad);
+        pads.press(pad, GamepadButton::South);
+        input.poll_gamepads();
+        assert_eq!(input.gamepads_connected(), &[pad]);
+        assert!(input.is_gamepad_button_pressed(pad, GamepadButton::South));
+        assert!(input.is_action_pressed("jump"));
+
+        input.next_frame();
+        input.poll_gamepads();
+        assert!(input.is_gamepad_button_down(pad, GamepadButton::South));
+        assert!(!input.is_action_pressed("jump"));
+
+        pads.release(pad, GamepadButton::South);
+        input.poll_gamepads();
+        assert!(input.is_gamepad_button_released(pad, GamepadButton::South));
+        assert!(input.is_action_released("jump"));
+    }
+
+    #[test]
+    fn sticks_have_a_deadzone() {
+        let (mut input, pads) = with_fake_pads();
+        let pad = GamepadId(0);
+        pads.connect(pad);
+        pads.move_axis(pad, GamepadAxis::LeftStickX, 0.1);
+        input.poll_gamepads();
+        assert_eq!(input.gamepad_axis(pad, GamepadAxis::LeftStickX), 0.0);
+        assert_eq!(input.action_axis("move_x"), 0.0);
+
+        pads.move_axis(pad, GamepadAxis::LeftStickX, -0.6);
+        input.poll_gamepads();
+        assert!((input.action_axis("move_x") + 0.5).abs() < 1e-5);
+        pads.move_axis(pad, GamepadAxis::LeftStickX, 1.0);
+        input.poll_gamepads();
+        assert_eq!(input.action_axis("move_x"), 1.0);
+        assert!(input.is_binding_down(Binding::GamepadAxis(
+            GamepadAxis::LeftStickX,
+            AxisDirection::Positive
+        )));
+    }
+
+    #[test]
+    fn players_get_pads_in_connection_order() {
+        let (mut input, pads) = with_fake_pads();
+        pads.connect(GamepadId(3));
+        pads.connect(GamepadId(1));
+        input.poll_gamepads();
+        assert_eq!(input.player_gamepad(0), Some(GamepadId(3)));
+        assert_eq!(input.player_gamepad(1), Some(GamepadId(1)));
+
+        input.next_frame();
+        pads.disconnect(GamepadId(3));
+        pads.press(GamepadId(3), GamepadButton::South);
+        input.poll_gamepads();
+        assert_eq!(input.gamepads_disconnected(), &[GamepadId(3)]);
+        assert_eq!(input.player_gamepad(0), None);
+        assert!(!input.is_action_down("jump"));
+
+        // A new pad takes the empty slot, and players can swap
+        pads.connect(GamepadId(4));
+        input.poll_gamepads();
+        assert_eq!(input.player_gamepad(0), Some(GamepadId(4)));
+        input.assign_gamepad(0, GamepadId(1));
+        assert_eq!(input.player_gamepad(0), Some(GamepadId(1)));
+        assert_eq!(input.player_gamepad(1), Some(GamepadId(4)));
+    }
+
+    #[test]
+    fn players_have_their_own_bindings_and_pads() {
+        let (mut input, pads) = with_fake_pads();
+        let mut wasd = ActionMap::new();
+        wasd.bind("jump", Key::W).bind("jump", GamepadButton::South);
+        wasd.bind_axis("move_x", Key::A, Key::D);
+        input.set_player_actions(1, wasd);
+        pads.connect(GamepadId(0));
+        pads.connect(GamepadId(1));
+        input.poll_gamepads();
+
+        input.set_key(Key::W, true);
+        assert!(!input.player(0).is_action_down("jump"));
+        assert!(input.player(1).is_action_down("jump"));
+
+        // Player one's pad doesn't move player two
+        pads.press(GamepadId(0), GamepadButton::South);
+        pads.move_axis(GamepadId(0), GamepadAxis::LeftStickX, 1.0);
+        input.poll_gamepads();
+        assert!(input.player(0).is_action_pressed("jump"));
+        assert_eq!(input.player(0).action_axis("move_x"), 1.0);
+        assert_eq!(input.player(1).action_axis("move_x"), 0.0);
+        input.set_key(Key::W, false);
+        assert!(!input.player(1).is_action_down("jump"));
+
+        // Nobody set up player three
+        assert!(!input.player(2).is_action_down("jump"));
+        assert!(input.player(2).actions().is_none());
+    }
+
+    #[test]
+    fn players_can_leave_the_keyboard_to_others() {
+        let (mut input, pads) = with_fake_pads();
+        input.set_player_actions(1, input.actions().clone());
+        pads.connect(GamepadId(5));
+        input.poll_gamepads();
+        input.assign_gamepad(1, GamepadId(5));
+        input.set_player_keyboard(1, false);
+        assert_eq!(input.player_gamepad(0), None);
+        assert!(!input.player_keyboard(1));
+
+        // The same keys only move player one now
+        input.set_key(Key::Right, true);
+        input.set_key(Key::Space, true);
+        assert_eq!(input.player(0).action_axis("move_x"), 1.0);
+        assert_eq!(input.player(1).action_axis("move_x"), 0.0);
+        assert!(input.player(0).is_action_down("jump"));
+        assert!(!input.player(1).is_action_down("jump"));
+
+        // And the pad only moves player two
+        pads.move_axis(GamepadId(5), GamepadAxis::LeftStickX, -1.0);
+        pads.press(GamepadId(5), GamepadButton::South);
+        input.poll_gamepads();
+        assert_eq!(input.player(0).action_axis("move_x"), 1.0);
+        assert_eq!(input.player(1).action_axis("move_x"), -1.0);
+        assert!(input.player(1).is_action_pressed("jump"));
+        // The whole-game view still sees everything
+        assert!(input.is_binding_down(Binding::Key(Key::Space)));
+    }
+
+    #[test]
+    fn extra_mouse_buttons_are_their_own_buttons() {
+        let mut input = Input::default();
+        input.handle_mouse_button(ElementState::Pressed, MouseButton::Other(0));
+        assert!(input.is_mouse_pressed(MouseButton::Other(0)));
+        assert!(!input.is_mouse_down(MouseButton::Left));
+        assert_eq!(
+            input.pressed_bindings(),
+            &[Binding::Mouse(MouseButton::Other(0))]
+        );
+
+        // Too far out to keep track of, but no panic either
+        input.handle_mouse_button(ElementState::Pressed, MouseButton::Other(40));
+        assert!(!input.is_mouse_down(MouseButton::Other(40)));
+        assert!(input.is_mouse_up(MouseButton::Other(40)));
+        assert!(!input.is_binding_down(Binding::Mouse(MouseButton::Other(40))));
+        input.handle_mouse_button(ElementState::Released, MouseButton::Other(40));
+        input.next_frame();
+        assert!(!input.is_mouse_released(MouseButton::Other(40)));
+
+        input.handle_mouse_button(ElementState::Released, MouseButton::Other(0));
+        assert!(input.is_mouse_released(MouseButton::Other(0)));
+    }
+
+    #[test]
+    fn cursor_in_world_coordinates() {
+        let mut input = Input::default();
+        input.handle_resize(PhysicalSize::new(2048, 1536));
+        let camera = GPUCamera {
+            screen_pos: [100.0, 0.0],
+            screen_size: [1024.0, 768.0],
+        };
+        input.handle_mouse_move(MousePos::new(0.0, 1536.0));
+        assert_eq!(input.mouse_world_pos(&camera), [100.0, 0.0]);
+        input.next_frame();
+        // Halfway across, a quarter of the way down
+        input.handle_mouse_move(MousePos::new(1024.0, 384.0));
+        assert_eq!(input.mouse_world_pos(&camera), [612.0, 576.0]);
+        assert_eq!(input.mouse_world_delta(&camera), [512.0, 576.0]);
+    }
+
+    #[test]
+    fn wheel_and_cursor_edges_last_one_frame() {
+        let mut input = Input::default();
+        input.handle_cursor_inside(true);
+        input.handle_mouse_wheel(MouseScrollDelta::LineDelta(0.0, 1.0));
+        input.handle_mouse_wheel(MouseScrollDelta::PixelDelta(
+            winit::dpi::PhysicalPosition::new(0.0, 40.0),
+        ));
+        assert!(input.cursor_entered());
+        assert_eq!(input.mouse_wheel(), [0.0, 3.0]);
+        input.next_frame();
+        assert!(!input.cursor_entered());
+        assert_eq!(input.mouse_wheel(), [0.0, 0.0]);
+        input.handle_cursor_inside(false);
+        assert!(input.cursor_left());
+        assert!(!input.is_cursor_in_window());
+    }
+
+    #[test]
+    fn controls_round_trip_through_json() {
+        let actions = controls().actions().clone();
+        let json = serde_json::to_string(&actions).unwrap();
+        assert!(json.contains(r#"{"key":"Space"}"#), "{}", json);
+        assert_eq!(serde_json::from_str::<ActionMap>(&json).unwrap(), actions);
     }
 }
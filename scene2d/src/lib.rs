@@ -1,14 +1,51 @@
 mod gpu;
 mod input;
+pub use input::{Input, Key, MousePos, PlayerInput};
+mod actions;
+pub use actions::{ActionMap, ActionMapError, AxisBinding, AxisDirection, Binding};
+mod gamepad;
+pub use gamepad::{
+    FakeGamepads, GamepadAxis, GamepadBackend, GamepadButton, GamepadEvent, GamepadId,
+    GilrsGamepads,
+};
 mod sprite;
-pub use sprite::{GPUCamera, GPUSprite};
+pub use sprite::{BlendMode, GPUCamera, GPUSprite, SpriteError, SpriteGroupId, SpriteRender};
 
 pub use gpu::WGPU;
 mod engine;
 pub use engine::Engine;
+mod text;
+pub use text::{
+    measure_text, Align, BitmapFont, Font, Glyph, NumberDisplay, NumberFormat, TextLabel,
+    TextLayout,
+};
+mod atlas;
+pub use atlas::{AtlasBuilder, AtlasError, TextureAtlas};
+mod ttf;
+pub use ttf::{FontError, TtfFont};
+mod assets;
+pub use assets::{Assets, Handle};
+mod animation;
+mod reload;
+mod timestep;
+pub use animation::{AnimationClip, Animator, Frame, PlayMode};
+mod aseprite;
+pub use aseprite::{AsepriteError, AsepriteSheet};
+mod tilemap;
+pub use tilemap::{
+    Layer, MapObject, ObjectLayer, Properties, Property, Shape, TileLayer, TileMap, TileMapError,
+    Tileset,
+};
+mod scene;
+pub use scene::{Scene, SceneError, SpawnedScene};
 
 #[async_trait::async_trait]
 pub trait Game {
     async fn init(&mut self, engine: &mut Engine);
-    fn update(&mut self, engine: &mut Engine);
+    // Called at a fixed rate (see Engine::set_tick_rate), zero or more times a frame; `dt` is
+    // the length of a tick in seconds and is the same every time.
+    fn update(&mut self, engine: &mut Engine, dt: f32);
+    // Called once per frame after the ticks, before drawing. `alpha` is how far (0 to 1) the
+    // frame falls between the last tick and the next, for smoothing positions between ticks.
+    fn render(&mut self, _engine: &mut Engine, _alpha: f32) {}
 }
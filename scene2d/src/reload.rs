@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Where shader.wgsl lives in the source tree, so edits to it can be picked up without a rebuild
+pub(crate) const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+
+// Don't hit the filesystem every single frame
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Notices when files change on disk by checking their modification times now and then
+pub(crate) struct Watcher {
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+impl Watcher {
+    pub(crate) fn new() -> Self {
+        Self {
+            modified: HashMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Whether it's been long enough since the last poll to look again
+    pub(crate) fn due(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        true
+    }
+
+    // Whether `path` changed since the last time it was checked. The first check of a path only
+    // remembers its time.
+    pub(crate) fn changed(&mut self, path: &Path) -> bool {
+        let Ok(modified) = std::fs::metadata(path).and_then(|meta| meta.modified()) else {
+            // Missing for a moment while an editor saves it; look again next time
+            return false;
+        };
+        match self.modified.insert(path.to_path_buf(), modified) {
+            Some(before) => before != modified,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn changes_are_seen_once() {
+        let path = std::env::temp_dir().join(format!("watcher-test-{}", std::process::id()));
+        let touch = |secs| {
+            File::options()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .and_then(|file| {
+                    file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+                })
+                .unwrap()
+        };
+        let mut watcher = Watcher::new();
+        touch(1000);
+        // The first look only remembers the time
+        assert!(!watcher.changed(&path));
+        assert!(!watcher.changed(&path));
+        touch(2000);
+        assert!(watcher.changed(&path));
+        assert!(!watcher.changed(&path));
+        // Gone for a moment mid-save isn't a change, and coming back with a new time is
+        std::fs::remove_file(&path).unwrap();
+        assert!(!watcher.changed(&path));
+        touch(3000);
+        assert!(watcher.changed(&path));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn polls_are_spaced_out() {
+        let mut watcher = Watcher::new();
+        assert!(!watcher.due());
+        watcher.last_poll -= POLL_INTERVAL;
+        assert!(watcher.due());
+        assert!(!watcher.due());
+    }
+}
@@ -0,0 +1,612 @@
+use crate::{BlendMode, Engine, GPUCamera, GPUSprite, Handle, SpriteGroupId, TextureAtlas};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    // Anything wrong with the file itself, from a missing comma to a texture name that isn't
+    // defined, with the 1-based line and column of the offending value
+    Invalid {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "couldn't read scene: {}", err),
+            SceneError::Invalid {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "bad scene at line {} column {}: {}",
+                line, column, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+impl From<serde_json::Error> for SceneError {
+    fn from(err: serde_json::Error) -> Self {
+        let (line, column) = (err.line(), err.column());
+        // serde_json tacks the position onto its message, and it's already in the fields
+        let message = err.to_string();
+        let suffix = format!(" at line {} column {}", line, column);
+        SceneError::Invalid {
+            line,
+            column,
+            message: message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string(),
+        }
+    }
+}
+
+// Sprite groups laid out in a JSON file instead of code, like
+//     {
+//       "textures": { "sky": "background.jpg", "sheet": { "atlas": "kiiiii.json" } },
+//       "cameras": { "main": { "screen_pos": [0, 0], "screen_size": [1024, 768] } },
+//       "groups": [{
+//         "name": "background", "texture": "sky", "camera": "main",
+//         "sprites": [{ "name": "sky", "rect": [0, 0, 1024, 768] }]
+//       }]
+//     }
+// Groups also take "layer", "visible" and "blend" ("opaque", {"cutout": 0.2},
+// "premultiplied_alpha" or "additive"). Sprites take "region" (a sheet_region, or a region
+// name when the texture is an atlas; the whole texture by default), "color", "rotation",
+// "pivot", "flip_x", "flip_y" and "visible". Named sprites can be looked up after spawning.
+pub struct Scene {
+    file: file::Scene,
+    // Kept for pointing errors found after parsing at the right line
+    source: String,
+    // Texture paths are relative to this
+    dir: PathBuf,
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    pub fn parse(json: &str, dir: &Path) -> Result<Self, SceneError> {
+        let scene = Scene {
+            file: serde_json::from_str(json)?,
+            source: json.to_string(),
+            dir: dir.to_path_buf(),
+        };
+        scene.check()?;
+        Ok(scene)
+    }
+
+    // Catches everything that can be caught without loading textures: names that don't refer
+    // to anything, names used twice and region names on textures that aren't atlases
+    fn check(&self) -> Result<(), SceneError> {
+        let mut group_names = HashSet::new();
+        let mut sprite_names = HashSet::new();
+        for (g, group) in self.file.groups.iter().enumerate() {
+            let at = |key: &'static str| [Step::Key("groups"), Step::Index(g), Step::Key(key)];
+            let Some(texture) = self.file.textures.get(&group.texture) else {
+                return Err(self.error_at(
+                    &at("texture"),
+                    format!("there's no texture named {:?}", group.texture),
+                ));
+            };
+            if !self.file.cameras.contains_key(&group.camera) {
+                return Err(self.error_at(
+                    &at("camera"),
+                    format!("there's no camera named {:?}", group.camera),
+                ));
+            }
+            if let Some(name) = &group.name {
+                if !group_names.insert(name) {
+                    return Err(self.error_at(
+                        &at("name"),
+                        format!("there's already a group named {:?}", name),
+                    ));
+                }
+            }
+            for (s, sprite) in group.sprites.iter().enumerate() {
+                let at = |key: &'static str| {
+                    [
+                        Step::Key("groups"),
+                        Step::Index(g),
+                        Step::Key("sprites"),
+                        Step::Index(s),
+                        Step::Key(key),
+                    ]
+                };
+                if let Some(name) = &sprite.name {
+                    if !sprite_names.insert(name) {
+                        return Err(self.error_at(
+                            &at("name"),
+                            format!("there's already a sprite named {:?}", name),
+                        ));
+                    }
+                }
+                if let (Some(file::Region::Named(region)), file::Texture::Image(_)) =
+                    (&sprite.region, texture)
+                {
+                    return Err(self.error_at(
+                        &at("region"),
+                        format!(
+                            "{:?} isn't an atlas, so region {:?} has to be [x, y, w, h]",
+                            group.texture, region
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Adds every group to the engine's SpriteRender, loading textures through its asset cache.
+    // If anything fails, the groups added so far are taken out again.
+    pub fn spawn(&self, engine: &mut Engine) -> Result<SpawnedScene, SceneError> {
+        let mut spawned = SpawnedScene {
+            groups: HashMap::new(),
+            sprites: HashMap::new(),
+            cameras: self
+                .file
+                .cameras
+                .iter()
+                .map(|(name, camera)| (name.clone(), camera.into()))
+                .collect(),
+        };
+        let mut added = Vec::new();
+        let result = self.spawn_groups(engine, &mut spawned, &mut added);
+        if result.is_err() {
+            for group in added {
+                let _ = engine.sprites.remove_sprite_group(group);
+            }
+        }
+        result.map(|()| spawned)
+    }
+
+    fn spawn_groups(
+        &self,
+        engine: &mut Engine,
+        spawned: &mut SpawnedScene,
+        added: &mut Vec<SpriteGroupId>,
+    ) -> Result<(), SceneError> {
+        let mut textures = HashMap::new();
+        for (name, texture) in &self.file.textures {
+            let loaded = match texture {
+                file::Texture::Image(path) => engine
+                    .texture(self.dir.join(path))
+                    .map(Loaded::Image)
+                    .map_err(|err| err.to_string()),
+                file::Texture::Atlas { atlas } => engine
+                    .assets
+                    .load(self.dir.join(atlas), |path| {
+                        TextureAtlas::load(&engine.gpu, path)
+                    })
+                    .map(Loaded::Atlas)
+                    .map_err(|err| err.to_string()),
+            };
+            let loaded = loaded.map_err(|err| {
+                self.error_at(
+                    &[Step::Key("textures"), Step::Key(name)],
+                    format!("couldn't load {:?}: {}", name, err),
+                )
+            })?;
+            textures.insert(name, loaded);
+        }
+
+        for (g, group) in self.file.groups.iter().enumerate() {
+            // check() made sure these are there
+            let texture = &textures[&group.texture];
+            let mut sprites = Vec::with_capacity(group.sprites.len());
+            for (s, sprite) in group.sprites.iter().enumerate() {
+                let sheet_region = match (&sprite.region, texture) {
+                    (None, _) => [0.0, 0.0, 1.0, 1.0],
+                    (Some(file::Region::Rect(region)), _) => *region,
+                    (Some(file::Region::Named(name)), Loaded::Atlas(atlas)) => {
+                        atlas.region(name).ok_or_else(|| {
+                            self.error_at(
+                                &[
+                                    Step::Key("groups"),
+                                    Step::Index(g),
+                                    Step::Key("sprites"),
+                                    Step::Index(s),
+                                    Step::Key("region"),
+                                ],
+                                format!("{:?} has no region named {:?}", group.texture, name),
+                            )
+                        })?
+                    }
+                    (Some(file::Region::Named(_)), Loaded::Image(_)) => {
+                        unreachable!("check() rejects region names on plain images")
+                    }
+                };
+                let mut gpu_sprite = GPUSprite::new(sprite.rect, sheet_region);
+                gpu_sprite.color = sprite.color;
+                gpu_sprite.rotation = sprite.rotation;
+                gpu_sprite.pivot = sprite.pivot;
+                gpu_sprite.set_flag(GPUSprite::FLIP_X, sprite.flip_x);
+                gpu_sprite.set_flag(GPUSprite::FLIP_Y, sprite.flip_y);
+                gpu_sprite.set_flag(GPUSprite::HIDDEN, !sprite.visible);
+                sprites.push(gpu_sprite);
+            }
+
+            let tex = match texture {
+                Loaded::Image(texture) => &**texture,
+                Loaded::Atlas(atlas) => atlas.texture(),
+            };
+            let camera = spawned.cameras[&group.camera];
+            let id = engine
+                .sprites
+                .add_sprite_group(&engine.gpu, tex, sprites, camera);
+            added.push(id);
+            engine
+                .sprites
+                .set_layer(id, group.layer)
+                .expect("group was just added");
+            engine
+                .sprites
+                .set_group_visible(id, group.visible)
+                .expect("group was just added");
+            if group.blend != BlendMode::default() {
+                engine
+                    .sprites
+                    .set_blend_mode(&engine.gpu, id, group.blend)
+                    .expect("group was just added");
+            }
+
+            if let Some(name) = &group.name {
+                spawned.groups.insert(name.clone(), id);
+            }
+            for (index, sprite) in group.sprites.iter().enumerate() {
+                if let Some(name) = &sprite.name {
+                    spawned.sprites.insert(name.clone(), (id, index));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn error_at(&self, path: &[Step], message: String) -> SceneError {
+        let (line, column) = locate(&self.source, path);
+        SceneError::Invalid {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+enum Loaded {
+    Image(Handle<wgpu::Texture>),
+    Atlas(Handle<TextureAtlas>),
+}
+
+// What a scene put into the SpriteRender, by the names the file gave it
+pub struct SpawnedScene {
+    groups: HashMap<String, SpriteGroupId>,
+    // The group each named sprite ended up in, and where in it
+    sprites: HashMap<String, (SpriteGroupId, usize)>,
+    cameras: HashMap<String, GPUCamera>,
+}
+
+impl SpawnedScene {
+    pub fn group(&self, name: &str) -> Option<SpriteGroupId> {
+        self.groups.get(name).copied()
+    }
+    pub fn sprite(&self, name: &str) -> Option<(SpriteGroupId, usize)> {
+        self.sprites.get(name).copied()
+    }
+    pub fn camera(&self, name: &str) -> Option<GPUCamera> {
+        self.cameras.get(name).copied()
+    }
+}
+
+// One step down into a JSON value: an object's field or an array's element
+enum Step<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+// Line and column (both from 1) where the value at `path` starts. serde only knows positions
+// while it's parsing, so this walks the text again. Stops at the deepest part of the path it
+// finds. Only ever called on text serde_json has already accepted.
+fn locate(source: &str, path: &[Step]) -> (usize, usize) {
+    let bytes = source.as_bytes();
+    let mut pos = skip_whitespace(bytes, 0);
+    for step in path {
+        match find_child(bytes, pos, step) {
+            Some(child) => pos = child,
+            None => break,
+        }
+    }
+    let before = &source[..pos];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+// Where `step`'s value starts inside the object or array starting at `pos`
+fn find_child(bytes: &[u8], pos: usize, step: &Step) -> Option<usize> {
+    let close = match (bytes.get(pos)?, step) {
+        (b'{', Step::Key(_)) => b'}',
+        (b'[', Step::Index(_)) => b']',
+        _ => return None,
+    };
+    let mut pos = skip_whitespace(bytes, pos + 1);
+    let mut index = 0;
+    while *bytes.get(pos)? != close {
+        let found = match step {
+            Step::Key(key) => {
+                let end = skip_value(bytes, pos);
+                let name = &bytes[pos + 1..end - 1];
+                pos = skip_whitespace(bytes, end);
+                // Past the colon
+                pos = skip_whitespace(bytes, pos + 1);
+                name == key.as_bytes()
+            }
+            Step::Index(want) => index == *want,
+        };
+        if found {
+            return Some(pos);
+        }
+        index += 1;
+        pos = skip_whitespace(bytes, skip_value(bytes, pos));
+        if bytes.get(pos) == Some(&b',') {
+            pos = skip_whitespace(bytes, pos + 1);
+        }
+    }
+    None
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+// Just past the end of the value starting at `pos`
+fn skip_value(bytes: &[u8], mut pos: usize) -> usize {
+    let mut depth = 0;
+    let mut in_string = false;
+    while let Some(&b) = bytes.get(pos) {
+        pos += 1;
+        if in_string {
+            match b {
+                b'\\' => pos += 1,
+                b'"' => {
+                    in_string = false;
+                    if depth == 0 {
+                        return pos;
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth == 0 => return pos - 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return pos;
+                }
+            }
+            b',' | b' ' | b'\t' | b'\r' | b'\n' if depth == 0 => return pos - 1,
+            _ => {}
+        }
+    }
+    pos
+}
+
+// The file's layout, as serde reads it
+mod file {
+    use super::*;
+
+    fn yes() -> bool {
+        true
+    }
+    fn white() -> [f32; 4] {
+        GPUSprite::WHITE
+    }
+    fn centre() -> [f32; 2] {
+        [0.5, 0.5]
+    }
+
+    // BTreeMaps so problems are always reported in the same order
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct Scene {
+        #[serde(default)]
+        pub(super) textures: BTreeMap<String, Texture>,
+        #[serde(default)]
+        pub(super) cameras: BTreeMap<String, Camera>,
+        #[serde(default)]
+        pub(super) groups: Vec<Group>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    pub(super) enum Texture {
+        Image(PathBuf),
+        Atlas { atlas: PathBuf },
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct Camera {
+        screen_pos: [f32; 2],
+        screen_size: [f32; 2],
+    }
+
+    impl From<&Camera> for GPUCamera {
+        fn from(camera: &Camera) -> Self {
+            GPUCamera {
+                screen_pos: camera.screen_pos,
+                screen_size: camera.screen_size,
+            }
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct Group {
+        #[serde(default)]
+        pub(super) name: Option<String>,
+        pub(super) texture: String,
+        pub(super) camera: String,
+        #[serde(default)]
+        pub(super) layer: i32,
+        #[serde(default = "yes")]
+        pub(super) visible: bool,
+        #[serde(default)]
+        pub(super) blend: BlendMode,
+        #[serde(default)]
+        pub(super) sprites: Vec<Sprite>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub(super) struct Sprite {
+        #[serde(default)]
+        pub(super) name: Option<String>,
+        pub(super) rect: [f32; 4],
+        #[serde(default)]
+        pub(super) region: Option<Region>,
+        #[serde(default = "white")]
+        pub(super) color: [f32; 4],
+        #[serde(default)]
+        pub(super) rotation: f32,
+        #[serde(default = "centre")]
+        pub(super) pivot: [f32; 2],
+        #[serde(default)]
+        pub(super) flip_x: bool,
+        #[serde(default)]
+        pub(super) flip_y: bool,
+        #[serde(default = "yes")]
+        pub(super) visible: bool,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    pub(super) enum Region {
+        Rect([f32; 4]),
+        Named(String),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Scene, SceneError> {
+        Scene::parse(json, Path::new(""))
+    }
+
+    fn error(json: &str) -> SceneError {
+        match parse(json) {
+            Ok(_) => panic!("scene should have been rejected"),
+            Err(err) => err,
+        }
+    }
+
+    fn position(err: SceneError) -> (usize, usize) {
+        match err {
+            SceneError::Invalid { line, column, .. } => (line, column),
+            SceneError::Io(err) => panic!("unexpected io error {}", err),
+        }
+    }
+
+    const GOOD: &str = r#"{
+  "textures": {
+    "sky": "background.jpg",
+    "sheet": { "atlas": "kiiiii.json" }
+  },
+  "cameras": { "main": { "screen_pos": [0, 0], "screen_size": [1024, 768] } },
+  "groups": [
+    {
+      "name": "background",
+      "texture": "sky",
+      "camera": "main",
+      "blend": "opaque",
+      "sprites": [{ "name": "sky", "rect": [0, 0, 1024, 768] }]
+    },
+    {
+      "texture": "sheet",
+      "camera": "main",
+      "layer": 2,
+      "blend": { "cutout": 0.5 },
+      "sprites": [
+        { "rect": [0, 0, 64, 64], "region": "platform" },
+        { "rect": [64, 0, 64, 64], "region": [0, 0, 0.5, 0.5], "flip_x": true }
+      ]
+    }
+  ]
+}"#;
+
+    #[test]
+    fn reads_a_whole_scene() {
+        let Ok(scene) = parse(GOOD) else {
+            panic!("scene should parse");
+        };
+        assert_eq!(scene.file.textures.len(), 2);
+        assert_eq!(scene.file.groups[0].blend, BlendMode::Opaque);
+        assert_eq!(scene.file.groups[1].blend, BlendMode::Cutout(0.5));
+        assert_eq!(scene.file.groups[1].layer, 2);
+        assert!(scene.file.groups[1].sprites[1].flip_x);
+    }
+
+    #[test]
+    fn syntax_errors_point_at_their_line() {
+        let broken = GOOD.replace("\"layer\": 2,", "\"layer\": 2");
+        assert_eq!(position(error(&broken)).0, 19);
+    }
+
+    #[test]
+    fn unknown_fields_point_at_their_line() {
+        let typo = GOOD.replace("\"flip_x\"", "\"flipx\"");
+        let err = error(&typo);
+        assert!(err.to_string().contains("flipx"), "{}", err);
+        assert_eq!(position(err).0, 22);
+    }
+
+    #[test]
+    fn missing_names_point_at_the_reference() {
+        let err = error(&GOOD.replace("\"texture\": \"sheet\"", "\"texture\": \"shet\""));
+        assert!(err.to_string().contains("shet"), "{}", err);
+        assert_eq!(position(err), (16, 18));
+
+        let err = error(&GOOD.replacen("\"camera\": \"main\"", "\"camera\": \"hud\"", 1));
+        assert_eq!(position(err), (11, 17));
+    }
+
+    #[test]
+    fn region_names_need_an_atlas() {
+        let err = error(&GOOD.replace("\"texture\": \"sheet\"", "\"texture\": \"sky\""));
+        assert_eq!(position(err), (21, 45));
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let err = error(&GOOD.replace(
+            "\"texture\": \"sheet\",",
+            "\"name\": \"background\", \"texture\": \"sheet\",",
+        ));
+        assert_eq!(position(err).0, 16);
+    }
+}
@@ -16,12 +16,24 @@ struct Camera {
     screen_size: vec2<f32>
 }
 
-// GPUSprite, from before
+// GPUSprite, from before, plus how to rotate/flip it
 struct GPUSprite {
     to_rect:vec4<f32>,
-    from_rect:vec4<f32>
+    from_rect:vec4<f32>,
+    // Fraction of to_rect to rotate around
+    pivot:vec2<f32>,
+    // Radians, counter-clockwise
+    rotation:f32,
+    flags:u32,
+    // Multiplied into the texture color
+    color:vec4<f32>
 }
 
+// Same bits as GPUSprite::FLIP_X and GPUSprite::FLIP_Y
+const FLIP_X:u32 = 1u;
+const FLIP_Y:u32 = 2u;
+const HIDDEN:u32 = 4u;
+
 // One binding for the camera...
 @group(0) @binding(0)
 var<uniform> camera: Camera;
@@ -29,10 +41,18 @@ var<uniform> camera: Camera;
 @group(0) @binding(1)
 var<storage, read> sprites: array<GPUSprite>;
 
+// Per-group settings for the fragment shaders, from the group's BlendMode
+struct Params {
+    alpha_cutoff: f32
+}
+@group(0) @binding(2)
+var<uniform> params: Params;
+
 // Same as before
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
 }
 
 @vertex
@@ -49,12 +69,32 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32,
     // Which corner of the square we need to draw now (in_vertex_index is in 0..6)
     let which_vtx:vec2<f32> = VERTICES[in_vertex_index];
     // Which corner of the UV square we need to draw (UV coordinates are flipped in Y)
-    let which_uv: vec2<f32> = vec2(VERTICES[in_vertex_index].x, 1.0 - VERTICES[in_vertex_index].y);
+    var which_uv: vec2<f32> = vec2(VERTICES[in_vertex_index].x, 1.0 - VERTICES[in_vertex_index].y);
+    // Flipping just means sampling from the opposite side of the sheet region
+    let flags:u32 = sprites[sprite_index].flags;
+    // Hidden sprites collapse to a single point, so there's nothing to rasterize
+    if (flags & HIDDEN) != 0u {
+        return VertexOutput(vec4(0.,0.,0.,1.), vec2(0.,0.), vec4(0.,0.,0.,0.));
+    }
+    if (flags & FLIP_X) != 0u { which_uv.x = 1.0 - which_uv.x; }
+    if (flags & FLIP_Y) != 0u { which_uv.y = 1.0 - which_uv.y; }
+    // Offset corner by size * which_vtx to get the right corner
+    var pos:vec2<f32> = corner.xy + which_vtx*size;
+    // Most sprites aren't rotated, so skip the trig for them
+    let rotation:f32 = sprites[sprite_index].rotation;
+    if rotation != 0.0 {
+        let pivot:vec2<f32> = corner.xy + sprites[sprite_index].pivot*size;
+        let c:f32 = cos(rotation);
+        let s:f32 = sin(rotation);
+        let d:vec2<f32> = pos - pivot;
+        pos = pivot + vec2(d.x*c - d.y*s, d.x*s + d.y*c);
+    }
     return VertexOutput(
-        // Offset corner by size * which_vtx to get the right corner, then do camera stuff. Dividing screen size by 2 and the last subtraction are to deal with the NDC coordinate space, which goes from -1 to 1 in WGPU.
-        ((corner + vec4(which_vtx*size,0.,0.) - vec4(camera.screen_pos,0.,0.)) / vec4(camera.screen_size/2., 1.0, 1.0)) - vec4(1.0, 1.0, 0.0, 0.0),
+        // Then do camera stuff. Dividing screen size by 2 and the last subtraction are to deal with the NDC coordinate space, which goes from -1 to 1 in WGPU.
+        ((vec4(pos,0.,1.) - vec4(camera.screen_pos,0.,0.)) / vec4(camera.screen_size/2., 1.0, 1.0)) - vec4(1.0, 1.0, 0.0, 0.0),
         // Offset texture corner by tex_size * which_uv to get the right corner
-        tex_corner + which_uv*tex_size
+        tex_corner + which_uv*tex_size,
+        sprites[sprite_index].color
     );
 }
 
@@ -68,12 +108,29 @@ var s_diffuse: sampler;
 // Both are in the same binding group here since they go together naturally.
 
 // Our fragment shader takes an interpolated `VertexOutput` as input now
+// This one is for BlendMode::Cutout
 @fragment
 fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
     // And we use the tex coords from the vertex output to sample from the texture.
     let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
     // This is new: if the alpha value of the color is very low, don't draw any fragment here.
-    // This is like "cutout" transparency.
-    if color.w < 0.2 { discard; }
-    return color;
+    // This is like "cutout" transparency. It looks at the texture's alpha, not the tint's, so
+    // a sprite fading out doesn't suddenly vanish.
+    if color.w < params.alpha_cutoff { discard; }
+    return color * in.color;
+}
+
+// BlendMode::Opaque: alpha is ignored, so just write the color
+@fragment
+fn fs_opaque(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
+    return vec4(color.rgb, 1.0);
+}
+
+// BlendMode::PremultipliedAlpha and BlendMode::Additive: our textures have straight alpha, so
+// premultiply here and let the blend state do the rest
+@fragment
+fn fs_premul(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
+    return vec4(color.rgb * color.a, color.a);
 }
\ No newline at end of file
@@ -1,13 +1,53 @@
 use crate::WGPU;
 use core::ops::Range;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
 
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct GPUSprite {
     pub screen_region: [f32; 4], // This is the area of the screen the sprite should take up, like a collision box
     // Textures with a bunch of sprites are often called "sprite sheets"
     pub sheet_region: [f32; 4], // Which part of the sheet to look at for the sprite ??
+    // Point the sprite rotates around, as a fraction of screen_region ([0.5, 0.5] is the centre)
+    pub pivot: [f32; 2],
+    pub rotation: f32, // Counter-clockwise, in radians. Only changes how it's drawn, not screen_region
+    pub flags: u32,    // GPUSprite::FLIP_X etc.
+    // RGBA the texture gets multiplied by; alpha is the sprite's opacity
+    pub color: [f32; 4],
+}
+
+impl GPUSprite {
+    // Mirror the texture horizontally/vertically inside screen_region
+    pub const FLIP_X: u32 = 1 << 0;
+    pub const FLIP_Y: u32 = 1 << 1;
+    // Not drawn, but keeps its geometry for when it's shown again
+    pub const HIDDEN: u32 = 1 << 2;
+
+    pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    // An unrotated, unflipped, untinted sprite that pivots around its centre
+    pub const fn new(screen_region: [f32; 4], sheet_region: [f32; 4]) -> Self {
+        Self {
+            screen_region,
+            sheet_region,
+            pivot: [0.5, 0.5],
+            rotation: 0.0,
+            flags: 0,
+            color: Self::WHITE,
+        }
+    }
+    pub fn set_flag(&mut self, flag: u32, on: bool) {
+        if on {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
 }
 
 #[repr(C)]
@@ -17,23 +57,144 @@ pub struct GPUCamera {
     pub screen_size: [f32; 2], // The size of our screen???
 }
 
+// How a sprite group's pixels combine with what's already been drawn. Each mode has its own
+// render pipeline. In scene files it's "opaque", {"cutout": 0.2}, "premultiplied_alpha" or
+// "additive".
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    // Ignores alpha entirely, good for backgrounds
+    Opaque,
+    // Throws away pixels whose texture alpha is under the threshold and alpha-blends the rest
+    Cutout(f32),
+    // Smooth transparency for soft edges, shadows and overlays
+    PremultipliedAlpha,
+    // Adds onto whatever is underneath, for glows and particles
+    Additive,
+}
+
+impl Default for BlendMode {
+    // What every group got before blend modes existed
+    fn default() -> Self {
+        BlendMode::Cutout(0.2)
+    }
+}
+
+impl BlendMode {
+    const ALL: [BlendMode; 4] = [
+        BlendMode::Opaque,
+        BlendMode::Cutout(0.0),
+        BlendMode::PremultipliedAlpha,
+        BlendMode::Additive,
+    ];
+
+    // Which of SpriteRender's pipelines draws this mode
+    fn pipeline_index(self) -> usize {
+        match self {
+            BlendMode::Opaque => 0,
+            BlendMode::Cutout(_) => 1,
+            BlendMode::PremultipliedAlpha => 2,
+            BlendMode::Additive => 3,
+        }
+    }
+    fn entry_point(self) -> &'static str {
+        match self {
+            BlendMode::Opaque => "fs_opaque",
+            BlendMode::Cutout(_) => "fs_main",
+            BlendMode::PremultipliedAlpha | BlendMode::Additive => "fs_premul",
+        }
+    }
+    fn blend_state(self) -> Option<wgpu::BlendState> {
+        match self {
+            BlendMode::Opaque => None,
+            BlendMode::Cutout(_) => Some(wgpu::BlendState::ALPHA_BLENDING),
+            BlendMode::PremultipliedAlpha => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+        }
+    }
+    fn params(self) -> GroupParams {
+        GroupParams {
+            alpha_cutoff: match self {
+                BlendMode::Cutout(threshold) => threshold,
+                _ => 0.0,
+            },
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+// Per-group uniform the fragment shaders read; matches `Params` in shader.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct GroupParams {
+    alpha_cutoff: f32,
+    _padding: [f32; 3],
+}
+
+// Handle returned by `SpriteRender::add_sprite_group`. It stops working once the group is
+// removed, even if a later group ends up reusing the same slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteGroupId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpriteError {
+    // The group was removed (or never belonged to this SpriteRender)
+    StaleGroup(SpriteGroupId),
+    // The group exists but doesn't have a sprite at that index
+    SpriteOutOfRange {
+        group: SpriteGroupId,
+        index: usize,
+        len: usize,
+    },
+}
+
+impl std::fmt::Display for SpriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpriteError::StaleGroup(id) => write!(
+                f,
+                "sprite group {} (generation {}) has been removed",
+                id.index, id.generation
+            ),
+            SpriteError::SpriteOutOfRange { group, index, len } => write!(
+                f,
+                "sprite {} is out of range for sprite group {} with {} sprites",
+                index, group.index, len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpriteError {}
+
 pub struct SpriteRender {
-    pipeline: wgpu::RenderPipeline,
-    pub groups: Vec<SpriteGroup>,
+    // One per BlendMode, see BlendMode::pipeline_index
+    pipelines: Vec<wgpu::RenderPipeline>,
+    pipeline_layout: wgpu::PipelineLayout,
+    groups: Vec<GroupSlot>,
+    // Slots of removed groups, reused by the next add_sprite_group
+    free_slots: Vec<usize>,
+    // Slots of the live groups, sorted by layer. Groups on the same layer draw in the order
+    // they were added.
+    draw_order: Vec<usize>,
+    // Groups drawing from the same texture share one bind group for it
+    texture_bind_groups: HashMap<wgpu::Id<wgpu::Texture>, Weak<wgpu::BindGroup>>,
     sprite_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl SpriteRender {
     pub fn new(wgpu: &WGPU) -> Self {
-        let shader = wgpu
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: None,
-                // Cow is a "copy on write" wrapper that abstracts over owned or borrowed memory.
-                // Here we just need to use it since wgpu wants "some text" to compile a shader from.
-                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-            });
         let texture_bind_group_layout =
             wgpu.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -115,6 +276,17 @@ impl SpriteRender {
                             // No count, not a buffer array binding
                             count: None,
                         },
+                        // The group's blend parameters
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -130,47 +302,176 @@ impl SpriteRender {
                 push_constant_ranges: &[],
             });
 
-        let pipeline = wgpu
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu.config.format.into())],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
+        let pipelines = create_pipelines(wgpu, &pipeline_layout, include_str!("shader.wgsl"));
         //Converting that CPU stuff to GPU stuff
 
         Self {
-            pipeline,
+            pipelines,
+            pipeline_layout,
             groups: Vec::default(),
+            free_slots: Vec::default(),
+            draw_order: Vec::default(),
+            texture_bind_groups: HashMap::default(),
             sprite_bind_group_layout,
             texture_bind_group_layout,
         }
     }
+    // Recompiles the pipelines from new WGSL source. If it doesn't compile the old pipelines
+    // are kept and the error is returned.
+    pub fn reload_shader(&mut self, gpu: &WGPU, source: &str) -> Result<(), wgpu::Error> {
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = create_pipelines(gpu, &self.pipeline_layout, source);
+        if let Some(err) = pollster::block_on(gpu.device.pop_error_scope()) {
+            return Err(err);
+        }
+        self.pipelines = pipelines;
+        Ok(())
+    }
+
     pub fn add_sprite_group(
         &mut self,
         gpu: &WGPU,
         tex: &wgpu::Texture,
         sprites: Vec<GPUSprite>,
         camera: GPUCamera,
-    ) {
+    ) -> SpriteGroupId {
+        let tex_bind_group = self.texture_bind_group(gpu, tex);
+
+        let buffer_camera = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<GPUCamera>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let blend_mode = BlendMode::default();
+        let buffer_params = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<GroupParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue
+            .write_buffer(&buffer_params, 0, bytemuck::bytes_of(&blend_mode.params()));
+
+        // Storage buffers can't be empty, so even an empty group gets room for one sprite
+        let capacity = sprites.len().max(1);
+        let (buffer_sprite, sprite_bind_group) =
+            self.create_sprite_buffer(gpu, &buffer_camera, &buffer_params, capacity);
+        gpu.queue
+            .write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
+
+        gpu.queue
+            .write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
+        let group = SpriteGroup {
+            sprite_buffer: buffer_sprite,
+            sprites,
+            capacity,
+            dirty: None,
+            tex_bind_group,
+            sprite_bind_group,
+            camera,
+            buffer_camera,
+            blend_mode,
+            buffer_params,
+            layer: 0,
+            visible: true,
+        };
+        let id = match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.groups[index];
+                slot.group = Some(group);
+                SpriteGroupId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.groups.push(GroupSlot {
+                    generation: 0,
+                    group: Some(group),
+                });
+                SpriteGroupId {
+                    index: self.groups.len() - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.draw_order.push(id.index);
+        self.sort_draw_order();
+        id
+    }
+
+    // Groups on higher layers are drawn on top of lower ones. Every group starts on layer 0.
+    pub fn set_layer(&mut self, which: SpriteGroupId, layer: i32) -> Result<(), SpriteError> {
+        self.group_mut(which)?.layer = layer;
+        self.sort_draw_order();
+        Ok(())
+    }
+    pub fn layer(&self, which: SpriteGroupId) -> Result<i32, SpriteError> {
+        Ok(self.group(which)?.layer)
+    }
+
+    // Hidden groups are skipped entirely by `render`
+    pub fn set_group_visible(
+        &mut self,
+        which: SpriteGroupId,
+        visible: bool,
+    ) -> Result<(), SpriteError> {
+        self.group_mut(which)?.visible = visible;
+        Ok(())
+    }
+    pub fn is_group_visible(&self, which: SpriteGroupId) -> Result<bool, SpriteError> {
+        Ok(self.group(which)?.visible)
+    }
+    // Shows or hides one sprite using its GPUSprite::HIDDEN flag
+    pub fn set_sprite_visible(
+        &mut self,
+        which: SpriteGroupId,
+        index: usize,
+        visible: bool,
+    ) -> Result<(), SpriteError> {
+        let sprite = self.get_sprite_mut(which, index)?;
+        sprite.set_flag(GPUSprite::HIDDEN, !visible);
+        Ok(())
+    }
+
+    // The frontmost sprite in the group whose screen_region holds `point`, for clicking on
+    // things. `point` is in the group camera's coordinates, like Input::mouse_world_pos.
+    // Hidden sprites and groups can't be hit, and rotation isn't taken into account.
+    pub fn sprite_at(
+        &self,
+        which: SpriteGroupId,
+        point: [f32; 2],
+    ) -> Result<Option<usize>, SpriteError> {
+        let group = self.group(which)?;
+        if !group.visible {
+            return Ok(None);
+        }
+        let [px, py] = point;
+        Ok(group.sprites.iter().rposition(|sprite| {
+            let [x, y, w, h] = sprite.screen_region;
+            !sprite.has_flag(GPUSprite::HIDDEN) && px >= x && px < x + w && py >= y && py < y + h
+        }))
+    }
+
+    // The bind group for `tex`, made the first time a group uses it
+    fn texture_bind_group(&mut self, gpu: &WGPU, tex: &wgpu::Texture) -> Arc<wgpu::BindGroup> {
+        // Forget the ones whose groups are all gone
+        self.texture_bind_groups
+            .retain(|_, bind_group| bind_group.strong_count() > 0);
+        if let Some(bind_group) = self
+            .texture_bind_groups
+            .get(&tex.global_id())
+            .and_then(Weak::upgrade)
+        {
+            return bind_group;
+        }
         let view_kingtex_king = tex.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler_kingtex_king = gpu
             .device
             .create_sampler(&wgpu::SamplerDescriptor::default());
-        let tex_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let bind_group = Arc::new(gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
             layout: &self.texture_bind_group_layout,
             entries: &[
@@ -184,19 +485,49 @@ impl SpriteRender {
                     resource: wgpu::BindingResource::Sampler(&sampler_kingtex_king),
                 },
             ],
-        });
+        }));
+        self.texture_bind_groups
+            .insert(tex.global_id(), Arc::downgrade(&bind_group));
+        bind_group
+    }
 
-        let buffer_sprite = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: bytemuck::cast_slice::<_, u8>(&sprites).len() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    fn sort_draw_order(&mut self) {
+        let groups = &self.groups;
+        // sort_by_key is stable, so groups that share a layer keep their relative order
+        self.draw_order
+            .sort_by_key(|&index| groups[index].group.as_ref().map_or(0, |group| group.layer));
+    }
 
-        let buffer_camera = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+    // Drops the group and frees its GPU buffers. Any copies of `which` become stale.
+    pub fn remove_sprite_group(&mut self, which: SpriteGroupId) -> Result<(), SpriteError> {
+        self.group(which)?;
+        let slot = &mut self.groups[which.index];
+        if let Some(group) = slot.group.take() {
+            group.sprite_buffer.destroy();
+            group.buffer_camera.destroy();
+            group.buffer_params.destroy();
+        }
+        slot.generation += 1;
+        self.free_slots.push(which.index);
+        self.draw_order.retain(|&index| index != which.index);
+        Ok(())
+    }
+
+    // Makes a storage buffer with room for `capacity` sprites and the bind group pointing at it.
+    fn create_sprite_buffer(
+        &self,
+        gpu: &WGPU,
+        buffer_camera: &wgpu::Buffer,
+        buffer_params: &wgpu::Buffer,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer_sprite = gpu.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: std::mem::size_of::<GPUCamera>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            size: (capacity * std::mem::size_of::<GPUSprite>()) as u64,
+            // COPY_SRC so the contents can be read back to check what actually got uploaded
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
         });
 
@@ -212,283 +543,520 @@ impl SpriteRender {
                     binding: 1,
                     resource: buffer_sprite.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer_params.as_entire_binding(),
+                },
             ],
         });
-        gpu.queue
-            .write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
+        (buffer_sprite, sprite_bind_group)
+    }
 
-        gpu.queue
-            .write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
-        self.groups.push(SpriteGroup {
-            sprite_buffer: buffer_sprite,
-            sprites,
-            tex_bind_group,
-            sprite_bind_group,
-            camera,
-            buffer_camera,
-        });
+    // Adds a sprite to the end of the group and returns its index. If the group's buffer is
+    // full it is reallocated at double the size and the whole group is uploaded on the next flush.
+    pub fn push_sprite(
+        &mut self,
+        gpu: &WGPU,
+        which: SpriteGroupId,
+        sprite: GPUSprite,
+    ) -> Result<usize, SpriteError> {
+        let group = self.group(which)?;
+        let index = group.sprites.len();
+        if index == group.capacity {
+            let capacity = (index * 2).max(1);
+            let (buffer_sprite, sprite_bind_group) = self.create_sprite_buffer(
+                gpu,
+                &group.buffer_camera,
+                &group.buffer_params,
+                capacity,
+            );
+            let group = self.group_mut(which)?;
+            group.sprite_buffer.destroy();
+            group.sprite_buffer = buffer_sprite;
+            group.sprite_bind_group = sprite_bind_group;
+            group.capacity = capacity;
+            group.sprites.push(sprite);
+            group.mark_dirty(0..index + 1);
+        } else {
+            let group = self.group_mut(which)?;
+            group.sprites.push(sprite);
+            group.mark_dirty(index..index + 1);
+        }
+        Ok(index)
+    }
 
-        // self.groups.len() - 1
+    // Removes a sprite, shifting the ones after it down so draw order is kept.
+    pub fn remove_sprite(
+        &mut self,
+        which: SpriteGroupId,
+        index: usize,
+    ) -> Result<GPUSprite, SpriteError> {
+        self.get_sprite_mut(which, index)?;
+        let group = self.group_mut(which)?;
+        let removed = group.sprites.remove(index);
+        group.mark_dirty(index..group.sprites.len());
+        Ok(removed)
+    }
+
+    // Removes a sprite by moving the last sprite into its place. Cheaper than `remove_sprite`
+    // but changes the order (and index) of that last sprite.
+    pub fn swap_remove_sprite(
+        &mut self,
+        which: SpriteGroupId,
+        index: usize,
+    ) -> Result<GPUSprite, SpriteError> {
+        self.get_sprite_mut(which, index)?;
+        let group = self.group_mut(which)?;
+        let removed = group.sprites.swap_remove(index);
+        if index < group.sprites.len() {
+            group.mark_dirty(index..index + 1);
+        }
+        Ok(removed)
+    }
+
+    pub fn contains_group(&self, which: SpriteGroupId) -> bool {
+        self.group(which).is_ok()
+    }
+
+    fn group(&self, which: SpriteGroupId) -> Result<&SpriteGroup, SpriteError> {
+        match self.groups.get(which.index) {
+            Some(GroupSlot {
+                generation,
+                group: Some(group),
+            }) if *generation == which.generation => Ok(group),
+            _ => Err(SpriteError::StaleGroup(which)),
+        }
+    }
+
+    fn group_mut(&mut self, which: SpriteGroupId) -> Result<&mut SpriteGroup, SpriteError> {
+        match self.groups.get_mut(which.index) {
+            Some(GroupSlot {
+                generation,
+                group: Some(group),
+            }) if *generation == which.generation => Ok(group),
+            _ => Err(SpriteError::StaleGroup(which)),
+        }
     }
 
     //pub fn print_group(&self) {}
-    pub fn set_camera(&mut self, gpu: &WGPU, index: usize, camera: GPUCamera) {
-        let sg = &mut self.groups[index];
+    pub fn set_camera(
+        &mut self,
+        gpu: &WGPU,
+        which: SpriteGroupId,
+        camera: GPUCamera,
+    ) -> Result<(), SpriteError> {
+        let sg = self.group_mut(which)?;
         sg.camera = camera;
 
         gpu.queue
             .write_buffer(&sg.buffer_camera, 0, bytemuck::bytes_of(&sg.camera));
+        Ok(())
     }
     pub fn set_camera_all(&mut self, gpu: &WGPU, camera: GPUCamera) {
-        for sg_index in 0..self.groups.len() {
-            self.set_camera(gpu, sg_index, camera);
+        for sg in self
+            .groups
+            .iter_mut()
+            .filter_map(|slot| slot.group.as_mut())
+        {
+            sg.camera = camera;
+            gpu.queue
+                .write_buffer(&sg.buffer_camera, 0, bytemuck::bytes_of(&sg.camera));
         }
     }
+    pub fn set_blend_mode(
+        &mut self,
+        gpu: &WGPU,
+        which: SpriteGroupId,
+        mode: BlendMode,
+    ) -> Result<(), SpriteError> {
+        let group = self.group_mut(which)?;
+        group.blend_mode = mode;
+        gpu.queue
+            .write_buffer(&group.buffer_params, 0, bytemuck::bytes_of(&mode.params()));
+        Ok(())
+    }
+    pub fn blend_mode(&self, which: SpriteGroupId) -> Result<BlendMode, SpriteError> {
+        Ok(self.group(which)?.blend_mode)
+    }
 
-    pub fn refresh_sprites(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
-        gpu.queue.write_buffer(
-            &self.groups[which].sprite_buffer,
-            range.start as u64,
-            bytemuck::cast_slice(&self.groups[which].sprites[range]),
-        )
+    pub fn refresh_sprites(
+        &mut self,
+        gpu: &WGPU,
+        which: SpriteGroupId,
+        range: Range<usize>,
+    ) -> Result<(), SpriteError> {
+        let group = self.group(which)?;
+        let len = group.sprites.len();
+        if range.start > range.end || range.end > len {
+            return Err(SpriteError::SpriteOutOfRange {
+                group: which,
+                index: range.end,
+                len,
+            });
+        }
+        group.upload(gpu, range);
+        Ok(())
     }
 
-    pub fn get_sprite_mut(&mut self, which: usize, range: usize) -> &mut GPUSprite {
-        &mut self.groups[which].sprites[range]
+    pub fn get_sprite_mut(
+        &mut self,
+        which: SpriteGroupId,
+        index: usize,
+    ) -> Result<&mut GPUSprite, SpriteError> {
+        let group = self.group_mut(which)?;
+        let len = group.sprites.len();
+        if index >= len {
+            return Err(SpriteError::SpriteOutOfRange {
+                group: which,
+                index,
+                len,
+            });
+        }
+        group.mark_dirty(index..index + 1);
+        Ok(&mut group.sprites[index])
     }
 
-    pub fn get_sprite_range_mut(&mut self, which: usize, range: Range<usize>) -> &mut [GPUSprite] {
-        &mut self.groups[which].sprites[range]
+    pub fn get_sprite_range_mut(
+        &mut self,
+        which: SpriteGroupId,
+        range: Range<usize>,
+    ) -> Result<&mut [GPUSprite], SpriteError> {
+        let group = self.group_mut(which)?;
+        let len = group.sprites.len();
+        if range.start > range.end || range.end > len {
+            return Err(SpriteError::SpriteOutOfRange {
+                group: which,
+                index: range.end,
+                len,
+            });
+        }
+        group.mark_dirty(range.clone());
+        Ok(&mut group.sprites[range])
     }
 
-    pub fn get_sprites(&self, which: usize) -> &[GPUSprite] {
-        &self.groups[which].sprites
+    pub fn get_sprites(&self, which: SpriteGroupId) -> Result<&[GPUSprite], SpriteError> {
+        Ok(&self.group(which)?.sprites)
     }
-    pub fn get_all_sprites_mut(&mut self, which: usize) -> &mut [GPUSprite] {
-        &mut self.groups[which].sprites
+    pub fn get_all_sprites_mut(
+        &mut self,
+        which: SpriteGroupId,
+    ) -> Result<&mut [GPUSprite], SpriteError> {
+        let group = self.group_mut(which)?;
+        group.mark_dirty(0..group.sprites.len());
+        Ok(&mut group.sprites)
     }
-    pub fn group_size(&self, which: usize) -> &[GPUSprite] {
-        &self.groups[which].sprites
+    pub fn group_size(&self, which: SpriteGroupId) -> Result<usize, SpriteError> {
+        Ok(self.group(which)?.sprites.len())
+    }
+
+    // Uploads every sprite changed through this SpriteRender since the last flush. The engine
+    // calls this once per frame right before rendering.
+    pub fn flush(&mut self, gpu: &WGPU) {
+        for group in self
+            .groups
+            .iter_mut()
+            .filter_map(|slot| slot.group.as_mut())
+        {
+            if let Some(range) = group.dirty.take() {
+                let end = range.end.min(group.sprites.len());
+                group.upload(gpu, range.start..end);
+            }
+        }
     }
 
     pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
     where
         's: 'pass,
     {
-        rpass.set_pipeline(&self.pipeline);
-        for group in self.groups.iter() {
+        let mut current_pipeline = None;
+        let mut current_texture: Option<&Arc<wgpu::BindGroup>> = None;
+        for group in self
+            .draw_order
+            .iter()
+            .filter_map(|&index| self.groups[index].group.as_ref())
+            .filter(|group| group.visible)
+        {
+            // Only switch pipelines when the blend mode actually changes
+            let pipeline = group.blend_mode.pipeline_index();
+            if current_pipeline != Some(pipeline) {
+                rpass.set_pipeline(&self.pipelines[pipeline]);
+                current_pipeline = Some(pipeline);
+            }
             // rpass.set_vertex_buffer(0, group.sprite_buffer.slice(0..10));
             //maybe take out of loop idk
 
             rpass.set_bind_group(0, &group.sprite_bind_group, &[]);
-            rpass.set_bind_group(1, &group.tex_bind_group, &[]);
+            // Groups sharing a texture can reuse the bind group already set
+            if !current_texture.is_some_and(|texture| Arc::ptr_eq(texture, &group.tex_bind_group)) {
+                rpass.set_bind_group(1, &group.tex_bind_group, &[]);
+                current_texture = Some(&group.tex_bind_group);
+            }
             rpass.draw(0..6, 0..(group.sprites.len() as u32));
         }
     }
 
-    pub fn update_position(&mut self, new_region: [f32; 4], sprite: usize) {
-        let the_sprite = self.get_sprite_mut(sprite, 0);
+    pub fn update_position(
+        &mut self,
+        new_region: [f32; 4],
+        sprite: SpriteGroupId,
+    ) -> Result<(), SpriteError> {
+        let the_sprite = self.get_sprite_mut(sprite, 0)?;
         the_sprite.screen_region = new_region;
+        Ok(())
     }
 
-    pub fn update_sprite(&mut self, new_region: [f32; 4], sprite: usize) {
-        let the_sprite = self.get_sprite_mut(sprite, 0);
+    pub fn update_sprite(
+        &mut self,
+        new_region: [f32; 4],
+        sprite: SpriteGroupId,
+    ) -> Result<(), SpriteError> {
+        let the_sprite = self.get_sprite_mut(sprite, 0)?;
         the_sprite.sheet_region = new_region;
+        Ok(())
     }
 
-    pub fn update_sprite_score(&mut self, new_region: [f32; 4], sprite: usize, index: usize) {
-        let the_sprite = self.get_sprite_mut(sprite, index);
+    pub fn update_sprite_score(
+        &mut self,
+        new_region: [f32; 4],
+        sprite: SpriteGroupId,
+        index: usize,
+    ) -> Result<(), SpriteError> {
+        let the_sprite = self.get_sprite_mut(sprite, index)?;
         the_sprite.sheet_region = new_region;
+        Ok(())
     }
+}
 
-    pub fn zero_sprite(&mut self, old_region: [f32; 4], sprite: usize, index: usize) {
-        let the_sprite = self.get_sprite_mut(sprite, index);
-        let new_region = [old_region[0], old_region[1], 0.0, 0.0];
-        the_sprite.screen_region = new_region;
-    }
+// One pipeline per BlendMode, all built from the same shader. They only differ in fragment
+// shader and blend state.
+fn create_pipelines(
+    wgpu: &WGPU,
+    pipeline_layout: &wgpu::PipelineLayout,
+    source: &str,
+) -> Vec<wgpu::RenderPipeline> {
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            // Cow is a "copy on write" wrapper that abstracts over owned or borrowed memory.
+            // Here we just need to use it since wgpu wants "some text" to compile a shader from.
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+        });
+    BlendMode::ALL
+        .iter()
+        .map(|mode| {
+            wgpu.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: mode.entry_point(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: wgpu.config.format,
+                            blend: mode.blend_state(),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        })
+        .collect()
+}
 
-    pub fn set_screen_size_sprite(&mut self, sprite: usize, index: usize, new_size: [f32; 2]) {
-        let the_sprite = self.get_sprite_mut(sprite, index);
-        let mut old_region = the_sprite.screen_region;
-        old_region[2] = new_size[0];
-        old_region[3] = new_size[1];
-        the_sprite.screen_region = old_region;
-    }
+struct GroupSlot {
+    generation: u32,
+    group: Option<SpriteGroup>,
+}
+
+pub struct SpriteGroup {
+    sprite_buffer: wgpu::Buffer,
+    sprites: Vec<GPUSprite>,
+    // How many sprites sprite_buffer has room for
+    capacity: usize,
+    // Sprites changed since the last flush, waiting to be uploaded
+    dirty: Option<Range<usize>>,
+    tex_bind_group: Arc<wgpu::BindGroup>,
+    sprite_bind_group: wgpu::BindGroup,
+    camera: GPUCamera,
+    buffer_camera: wgpu::Buffer,
+    blend_mode: BlendMode,
+    buffer_params: wgpu::Buffer,
+    layer: i32,
+    visible: bool,
+}
 
-    //Trying to make moving platforms that move back and foth
-    pub fn platform_move(&mut self) {
-        let all_platforms = self.get_all_sprites_mut(2);
-        for platform in all_platforms.iter_mut() {
-            platform.sheet_region[0] += 32.0;
+impl SpriteGroup {
+    // Writes sprites[range] to the same place in the storage buffer. The buffer offset is in
+    // bytes, so the sprite index has to be scaled by the size of a GPUSprite.
+    fn upload(&self, gpu: &WGPU, range: Range<usize>) {
+        if range.start >= range.end {
+            return;
         }
+        gpu.queue.write_buffer(
+            &self.sprite_buffer,
+            (range.start * std::mem::size_of::<GPUSprite>()) as u64,
+            bytemuck::cast_slice(&self.sprites[range]),
+        );
     }
 
-    // score must be less than 1000
-    pub fn update_score(&mut self, score_amount: usize) -> [[f32; 4]; 3] {
-        let mut score = score_amount;
-        let mut sprite_sheet_pos = [
-            [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-            [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-            [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0],
-        ];
-        loop {
-            if score < 10 {
-                if score == 9 {
-                    sprite_sheet_pos[2] = [64.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 8 {
-                    sprite_sheet_pos[2] = [56.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 7 {
-                    sprite_sheet_pos[2] = [48.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 6 {
-                    sprite_sheet_pos[2] = [40.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 5 {
-                    sprite_sheet_pos[2] = [32.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 4 {
-                    sprite_sheet_pos[2] = [24.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 3 {
-                    sprite_sheet_pos[2] = [16.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 2 {
-                    sprite_sheet_pos[2] = [8.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 1 {
-                    sprite_sheet_pos[2] = [0.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if score == 0 {
-                    sprite_sheet_pos[2] = [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                }
-                return sprite_sheet_pos;
-            } else if score < 100 {
-                let digit = score / 10;
-                if digit == 9 {
-                    sprite_sheet_pos[1] = [64.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 8 {
-                    sprite_sheet_pos[1] = [56.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 7 {
-                    sprite_sheet_pos[1] = [48.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 6 {
-                    sprite_sheet_pos[1] = [40.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 5 {
-                    sprite_sheet_pos[1] = [32.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 4 {
-                    sprite_sheet_pos[1] = [24.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 3 {
-                    sprite_sheet_pos[1] = [16.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 2 {
-                    sprite_sheet_pos[1] = [8.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 1 {
-                    sprite_sheet_pos[1] = [0.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 0 {
-                    sprite_sheet_pos[1] = [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                }
-                score %= 10;
-            } else {
-                let digit = score / 100;
-                if digit == 9 {
-                    sprite_sheet_pos[0] = [64.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 8 {
-                    sprite_sheet_pos[0] = [56.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 7 {
-                    sprite_sheet_pos[0] = [48.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 6 {
-                    sprite_sheet_pos[0] = [40.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 5 {
-                    sprite_sheet_pos[0] = [32.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 4 {
-                    sprite_sheet_pos[0] = [24.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 3 {
-                    sprite_sheet_pos[0] = [16.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 2 {
-                    sprite_sheet_pos[0] = [8.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 1 {
-                    sprite_sheet_pos[0] = [0.0 / 72.0, 48.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                } else if digit == 0 {
-                    sprite_sheet_pos[0] = [64.0 / 72.0, 40.0 / 88.0, 8.0 / 72.0, 8.0 / 88.0];
-                }
-                score %= 100;
-            }
-        }
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
     }
+}
 
-    pub fn check_collisions(&mut self, player: usize, which: usize) -> bool {
-        //The which for this function is the sprite group we want to go through
-        let player_sprite = self.get_sprites(player)[0]; //Getting the player sprite
-                                                         //Gettting and storing all the screen positions
-        let topleft = player_sprite.screen_region[0];
-        //let topright = player_sprite.screen_region[0] + player_sprite.screen_region[2];
-        let bottomleft = player_sprite.screen_region[1];
-        let bottomright = player_sprite.screen_region[1] + player_sprite.screen_region[3];
-
-        //Getting all sprites within said sprite group
-        let all_sprites = self.get_all_sprites_mut(which);
-        let index = 0;
-        for sprite in all_sprites.iter() {
-            //Looping through all of the sprites and determinign if their is any overlap within their positions.
-            let tl = sprite.screen_region[0];
-            let tr = sprite.screen_region[0] + sprite.screen_region[2];
-            let bl = sprite.screen_region[1];
-            let br = sprite.screen_region[1] + sprite.screen_region[3];
-
-            if topleft < tl || topleft > tr {
-                continue; // No horizontal overlap
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
 
-            if bottomright < bl || bottomleft > br {
-                continue; // No vertical overlap
-            }
+    fn sprite(n: f32) -> GPUSprite {
+        GPUSprite::new([n, n, 1.0, 1.0], [0.0, 0.0, 1.0, 1.0])
+    }
 
-            // println!("Collision");
+    // Sets up a headless WGPU and a SpriteRender with one group of four sprites numbered 0..4.
+    // Returns None (and the test passes trivially) if this machine has no adapter at all.
+    fn setup() -> Option<(WGPU, SpriteRender, SpriteGroupId)> {
+        let gpu = test_gpu()?;
+        let mut sprites = SpriteRender::new(&gpu);
+        let tex = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let camera = GPUCamera {
+            screen_pos: [0.0, 0.0],
+            screen_size: [1.0, 1.0],
+        };
+        let group = sprites.add_sprite_group(
+            &gpu,
+            &tex,
+            (0..4).map(|n| sprite(n as f32)).collect(),
+            camera,
+        );
+        Some((gpu, sprites, group))
+    }
 
-            //Then calling this seperate function in order to delete the sprite from the group
-            self.delete_sprite(which, index);
-            return true;
-        }
-        false
+    // Copies a group's storage buffer back to the CPU.
+    fn read_back(gpu: &WGPU, sprites: &SpriteRender, which: SpriteGroupId) -> Vec<GPUSprite> {
+        let group = sprites.group(which).unwrap();
+        let size = (group.sprites.len() * std::mem::size_of::<GPUSprite>()) as u64;
+        let staging = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&group.sprite_buffer, 0, &staging, 0, size);
+        gpu.queue.submit(Some(encoder.finish()));
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        gpu.device.poll(wgpu::Maintain::Wait);
+        let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        data
     }
 
-    pub fn player_collision(&mut self) -> bool {
-        let player1 = self.get_sprites(2)[0];
-        let player2 = self.get_sprites(3)[0];
+    fn xs(sprites: &[GPUSprite]) -> Vec<f32> {
+        sprites.iter().map(|s| s.screen_region[0]).collect()
+    }
 
-        //Comparing if they are overlapping in any way
-        //I have an offset of 60 pixels, becasue without the offset, they would collide way before actual;y interacting.  I just guessed and checked
-        if player1.screen_region[0] < player2.screen_region[0] + player2.screen_region[2] - 60.0
-            && player1.screen_region[0] + player1.screen_region[2] - 60.0 > player2.screen_region[0]
-            && player1.screen_region[1] < player2.screen_region[1] + player2.screen_region[3] - 60.0
-            && player1.screen_region[1] + player1.screen_region[3] - 60.0 > player2.screen_region[1]
+    #[test]
+    fn refresh_sprites_writes_sub_range_in_place() {
+        let Some((gpu, mut sprites, group)) = setup() else {
+            return;
+        };
+        sprites.flush(&gpu);
+        for (i, s) in sprites
+            .get_all_sprites_mut(group)
+            .unwrap()
+            .iter_mut()
+            .enumerate()
         {
-            return true;
+            s.screen_region[0] = 10.0 + i as f32;
         }
+        sprites.refresh_sprites(&gpu, group, 2..4).unwrap();
+        // Only sprites 2 and 3 were refreshed, and they landed at indices 2 and 3
+        assert_eq!(
+            xs(&read_back(&gpu, &sprites, group)),
+            [0.0, 1.0, 12.0, 13.0]
+        );
+    }
 
-        false
+    #[test]
+    fn refresh_sprites_single_sprite_in_the_middle() {
+        let Some((gpu, mut sprites, group)) = setup() else {
+            return;
+        };
+        sprites.flush(&gpu);
+        sprites.get_sprite_mut(group, 1).unwrap().screen_region[0] = 42.0;
+        sprites.refresh_sprites(&gpu, group, 1..2).unwrap();
+        assert_eq!(xs(&read_back(&gpu, &sprites, group)), [0.0, 42.0, 2.0, 3.0]);
     }
 
-    pub fn delete_sprite(&mut self, which: usize, sprite: usize) {
-        //We aren't really deleting it, just changing it's visibility and it's collision field
-        let group = &mut self.groups[which].sprites;
-        group[sprite].screen_region = [
-            group[sprite].screen_region[0],
-            group[sprite].screen_region[1],
-            0.0,
-            0.0,
-        ];
-        // group.remove(sprite);
+    #[test]
+    fn refresh_sprites_rejects_out_of_range() {
+        let Some((gpu, mut sprites, group)) = setup() else {
+            return;
+        };
+        assert_eq!(
+            sprites.refresh_sprites(&gpu, group, 3..5),
+            Err(SpriteError::SpriteOutOfRange {
+                group,
+                index: 5,
+                len: 4
+            })
+        );
     }
 
-    pub fn respawn_powerup(&mut self, which: usize) {
-        //If there is a sprite that isn't viisble within the powerup group, we simply toggle it back to being visible
-        let all_sprites = self.get_all_sprites_mut(which);
-        for sprites in all_sprites.iter_mut() {
-            if sprites.screen_region[2] == 0.0 {
-                sprites.screen_region[2] = 64.0;
-                sprites.screen_region[3] = 64.0;
-            }
-        }
+    #[test]
+    fn flush_uploads_only_dirty_sprites() {
+        let Some((gpu, mut sprites, group)) = setup() else {
+            return;
+        };
+        sprites.flush(&gpu);
+        sprites.update_sprite_score([0.0; 4], group, 3).unwrap();
+        sprites.get_sprite_mut(group, 3).unwrap().screen_region[0] = 7.0;
+        sprites.flush(&gpu);
+        assert_eq!(xs(&read_back(&gpu, &sprites, group)), [0.0, 1.0, 2.0, 7.0]);
     }
-}
 
-pub struct SpriteGroup {
-    sprite_buffer: wgpu::Buffer,
-    sprites: Vec<GPUSprite>,
-    tex_bind_group: wgpu::BindGroup,
-    sprite_bind_group: wgpu::BindGroup,
-    camera: GPUCamera,
-    buffer_camera: wgpu::Buffer,
+    #[test]
+    fn groups_with_the_same_texture_share_a_bind_group() {
+        let Some((gpu, mut sprites, first)) = setup() else {
+            return;
+        };
+        let camera = sprites.group(first).unwrap().camera;
+        let tex = gpu.create_texture(&image::RgbaImage::new(1, 1), None);
+        let other_tex = gpu.create_texture(&image::RgbaImage::new(1, 1), None);
+        let a = sprites.add_sprite_group(&gpu, &tex, vec![sprite(0.0)], camera);
+        let b = sprites.add_sprite_group(&gpu, &tex, vec![sprite(1.0)], camera);
+        let c = sprites.add_sprite_group(&gpu, &other_tex, vec![sprite(2.0)], camera);
+        let bind_group = |which| sprites.group(which).unwrap().tex_bind_group.clone();
+        assert!(Arc::ptr_eq(&bind_group(a), &bind_group(b)));
+        assert!(!Arc::ptr_eq(&bind_group(a), &bind_group(c)));
+        assert!(!Arc::ptr_eq(&bind_group(a), &bind_group(first)));
+    }
 }
@@ -0,0 +1,4 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="wide" tilewidth="32" tileheight="16" tilecount="4" columns="2">
+ <image source="walker.png" width="64" height="32"/>
+</tileset>
@@ -0,0 +1,630 @@
+use crate::{
+    BlendMode, GPUCamera, GPUSprite, Handle, SpriteError, SpriteGroupId, SpriteRender, WGPU,
+};
+use std::collections::HashMap;
+
+// Where one character comes from in a font's texture and how it sits on the line. Sizes are in
+// ems, so that TextLayout::glyph_size decides how big the text ends up on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glyph {
+    pub sheet_region: [f32; 4], // Same units as GPUSprite::sheet_region
+    pub offset: [f32; 2],       // Bottom left of the glyph, relative to the pen
+    pub size: [f32; 2],         // Zero for glyphs with nothing to draw, like spaces
+    pub advance: f32,           // How far the pen moves for the next glyph
+}
+
+// Anything TextLabel can draw text with: BitmapFont for grid sheets like font.png, TtfFont for
+// .ttf/.otf files.
+pub trait Font {
+    // The texture every glyph's sheet_region points into
+    fn texture(&self) -> &wgpu::Texture;
+    // None if the font has no such character. Takes the GPU since some fonts only rasterize
+    // glyphs the first time they're asked for.
+    fn glyph(&mut self, gpu: &WGPU, c: char) -> Option<Glyph>;
+    // Extra space (usually negative) between `left` and `right`, in ems
+    fn kerning(&self, _left: char, _right: char) -> f32 {
+        0.0
+    }
+    // Distance from one line to the next, in ems
+    fn line_height(&self) -> f32 {
+        1.0
+    }
+    // What labels using this font should be drawn with
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::default()
+    }
+}
+
+// Describes a texture laid out as a grid of equally sized glyphs, like font.png
+pub struct BitmapFont {
+    texture: Handle<wgpu::Texture>,
+    sheet_size: [f32; 2],
+    cell_size: [f32; 2],
+    // Which cell (counting left to right, top to bottom) each character is in
+    glyphs: HashMap<char, usize>,
+}
+
+impl BitmapFont {
+    // `layout` lists the characters in the same order as the cells in the sheet. Use a space
+    // for cells that should be skipped; characters that aren't in the layout are drawn as gaps.
+    pub fn grid(
+        texture: impl Into<Handle<wgpu::Texture>>,
+        cell_size: [u32; 2],
+        layout: &str,
+    ) -> Self {
+        let texture = texture.into();
+        let glyphs = layout
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| *c != ' ')
+            .map(|(cell, c)| (c, cell))
+            .collect();
+        let size = texture.size();
+        Self {
+            texture,
+            sheet_size: [size.width as f32, size.height as f32],
+            cell_size: [cell_size[0] as f32, cell_size[1] as f32],
+            glyphs,
+        }
+    }
+
+    // The part of the sheet `c` is drawn from, in the same units as GPUSprite::sheet_region
+    pub fn sheet_region(&self, c: char) -> Option<[f32; 4]> {
+        let cell = *self.glyphs.get(&c)?;
+        let columns = (self.sheet_size[0] / self.cell_size[0]) as usize;
+        let x = (cell % columns) as f32 * self.cell_size[0];
+        let y = (cell / columns) as f32 * self.cell_size[1];
+        Some([
+            x / self.sheet_size[0],
+            y / self.sheet_size[1],
+            self.cell_size[0] / self.sheet_size[0],
+            self.cell_size[1] / self.sheet_size[1],
+        ])
+    }
+}
+
+impl Font for BitmapFont {
+    fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    // Every glyph fills exactly one em
+    fn glyph(&mut self, _gpu: &WGPU, c: char) -> Option<Glyph> {
+        Some(Glyph {
+            sheet_region: self.sheet_region(c)?,
+            offset: [0.0, 0.0],
+            size: [1.0, 1.0],
+            advance: 1.0,
+        })
+    }
+}
+
+// Where and how big a TextLabel's glyphs are drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextLayout {
+    pub position: [f32; 2],   // Bottom left corner of the first line
+    pub glyph_size: [f32; 2], // Size of one em on screen
+    pub spacing: f32,         // Extra gap between one glyph and the next
+    // Lines longer than this are broken between words. Newlines always start a new line.
+    pub wrap_width: Option<f32>,
+}
+
+// Text drawn as its own sprite group, one sprite per character. Sprite `i` of the group is
+// always character `i` of the text; spaces, newlines and unknown characters are hidden sprites.
+// The label doesn't keep its font, so pass the same one back in when changing it.
+pub struct TextLabel {
+    group: SpriteGroupId,
+    layout: TextLayout,
+    text: String,
+}
+
+impl TextLabel {
+    pub fn new(
+        sprites: &mut SpriteRender,
+        gpu: &WGPU,
+        font: &mut dyn Font,
+        layout: TextLayout,
+        text: &str,
+        camera: GPUCamera,
+    ) -> Self {
+        let glyphs = layout_text(font, gpu, &layout, text);
+        let group = sprites.add_sprite_group(gpu, font.texture(), glyphs, camera);
+        sprites
+            .set_blend_mode(gpu, group, font.blend_mode())
+            .expect("group was just added");
+        Self {
+            group,
+            layout,
+            text: text.to_string(),
+        }
+    }
+
+    // The label's sprite group, e.g. for set_layer or set_group_visible
+    pub fn group(&self) -> SpriteGroupId {
+        self.group
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn layout(&self) -> TextLayout {
+        self.layout
+    }
+
+    pub fn set_text(
+        &mut self,
+        sprites: &mut SpriteRender,
+        gpu: &WGPU,
+        font: &mut dyn Font,
+        text: &str,
+    ) -> Result<(), SpriteError> {
+        self.text = text.to_string();
+        self.relayout(sprites, gpu, font)
+    }
+
+    // Moves, resizes or rewraps the text
+    pub fn set_layout(
+        &mut self,
+        sprites: &mut SpriteRender,
+        gpu: &WGPU,
+        font: &mut dyn Font,
+        layout: TextLayout,
+    ) -> Result<(), SpriteError> {
+        self.layout = layout;
+        self.relayout(sprites, gpu, font)
+    }
+
+    // Only the sprites that actually changed are touched, and the group grows or shrinks to fit
+    fn relayout(
+        &mut self,
+        sprites: &mut SpriteRender,
+        gpu: &WGPU,
+        font: &mut dyn Font,
+    ) -> Result<(), SpriteError> {
+        let glyphs = layout_text(font, gpu, &self.layout, &self.text);
+        let old_len = sprites.group_size(self.group)?;
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if i >= old_len {
+                sprites.push_sprite(gpu, self.group, *glyph)?;
+            } else if sprites.get_sprites(self.group)?[i] != *glyph {
+                *sprites.get_sprite_mut(self.group, i)? = *glyph;
+            }
+        }
+        for i in (glyphs.len()..old_len).rev() {
+            sprites.remove_sprite(self.group, i)?;
+        }
+        Ok(())
+    }
+}
+
+// How far the pen moves for each character, including kerning and spacing, in screen units
+fn advances(
+    font: &mut dyn Font,
+    gpu: &WGPU,
+    layout: &TextLayout,
+    text: &str,
+) -> Vec<(char, Option<Glyph>, f32)> {
+    let mut prev = None;
+    text.chars()
+        .map(|c| {
+            let glyph = font.glyph(gpu, c);
+            let kerning = prev.map_or(0.0, |prev| font.kerning(prev, c));
+            prev = Some(c);
+            // Unknown characters still take up a cell, like they did in the sheet
+            let advance = glyph.map_or(1.0, |glyph| glyph.advance) + kerning;
+            (c, glyph, advance * layout.glyph_size[0] + layout.spacing)
+        })
+        .collect()
+}
+
+// Width of the widest line of `text`, not counting the spacing after the last glyph
+pub fn measure_text(font: &mut dyn Font, gpu: &WGPU, layout: &TextLayout, text: &str) -> f32 {
+    let chars = advances(font, gpu, layout, text);
+    layout_lines(&chars, layout)
+        .iter()
+        .map(|&(start, end)| {
+            let width: f32 = chars[start..end]
+                .iter()
+                .map(|&(_, _, advance)| advance)
+                .sum();
+            (width - layout.spacing).max(0.0)
+        })
+        .fold(0.0, f32::max)
+}
+
+// Splits the text into lines, as ranges of character indices
+fn layout_lines(chars: &[(char, Option<Glyph>, f32)], layout: &TextLayout) -> Vec<(usize, usize)> {
+    let mut lines = vec![];
+    let mut start = 0;
+    let mut x = 0.0;
+    for i in 0..chars.len() {
+        let (c, _, advance) = chars[i];
+        if c == '\n' {
+            lines.push((start, i));
+            start = i + 1;
+            x = 0.0;
+            continue;
+        }
+        let word_start = !c.is_whitespace() && (i == 0 || chars[i - 1].0.is_whitespace());
+        if let (true, Some(wrap_width)) = (word_start && i > start, layout.wrap_width) {
+            let word: f32 = chars[i..]
+                .iter()
+                .take_while(|(c, _, _)| !c.is_whitespace())
+                .map(|&(_, _, advance)| advance)
+                .sum();
+            if x + word > wrap_width {
+                lines.push((start, i));
+                start = i;
+                x = 0.0;
+            }
+        }
+        x += advance;
+    }
+    lines.push((start, chars.len()));
+    lines
+}
+
+// One sprite per character of `text`
+fn layout_text(font: &mut dyn Font, gpu: &WGPU, layout: &TextLayout, text: &str) -> Vec<GPUSprite> {
+    let chars = advances(font, gpu, layout, text);
+    let line_height = font.line_height() * layout.glyph_size[1];
+    let mut sprites = Vec::with_capacity(chars.len());
+    for (line, (start, end)) in layout_lines(&chars, layout).into_iter().enumerate() {
+        let mut x = layout.position[0];
+        let y = layout.position[1] - line as f32 * line_height;
+        // The newline that ended the previous line is drawn as nothing
+        while sprites.len() < start {
+            sprites.push(hidden([x, y, 0.0, 0.0]));
+        }
+        for &(_, glyph, advance) in &chars[start..end] {
+            sprites.push(match glyph {
+                Some(glyph) if glyph.size != [0.0, 0.0] => GPUSprite::new(
+                    [
+                        x + glyph.offset[0] * layout.glyph_size[0],
+                        y + glyph.offset[1] * layout.glyph_size[1],
+                        glyph.size[0] * layout.glyph_size[0],
+                        glyph.size[1] * layout.glyph_size[1],
+                    ],
+                    glyph.sheet_region,
+                ),
+                _ => hidden([x, y, layout.glyph_size[0], layout.glyph_size[1]]),
+            });
+            x += advance;
+        }
+    }
+    sprites
+}
+
+fn hidden(screen_region: [f32; 4]) -> GPUSprite {
+    let mut sprite = GPUSprite::new(screen_region, [0.0; 4]);
+    sprite.set_flag(GPUSprite::HIDDEN, true);
+    sprite
+}
+
+// Which end of a NumberDisplay stays put as the number gets longer or shorter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    // The layout's position is the bottom left of the first glyph
+    Left,
+    // The layout's position is the bottom right of the last glyph
+    Right,
+}
+
+// How a NumberDisplay turns its value into text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub min_digits: usize,   // Shorter numbers are padded up to this many digits
+    pub leading_zeros: bool, // Pad with zeros instead of blank space
+    pub separator: Option<char>, // Put between every group of three digits, e.g. Some(',')
+    pub align: Align,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            min_digits: 1,
+            leading_zeros: false,
+            separator: None,
+            align: Align::Left,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn format(&self, value: i64) -> String {
+        let digits = value.unsigned_abs().to_string();
+        let pad = if self.leading_zeros { '0' } else { ' ' };
+        let padded: Vec<char> =
+            std::iter::repeat_n(pad, self.min_digits.saturating_sub(digits.len()))
+                .chain(digits.chars())
+                .collect();
+        let mut text = String::new();
+        for (i, c) in padded.iter().enumerate() {
+            let digits_left = padded.len() - i;
+            // Separators only go between digits, not into blank padding
+            if let Some(separator) = self.separator {
+                if i > 0 && digits_left.is_multiple_of(3) && padded[i - 1] != ' ' {
+                    text.push(separator);
+                }
+            }
+            text.push(*c);
+        }
+        if value < 0 {
+            // The sign sits right in front of the number, after any blank padding
+            let sign_at = text.len() - text.trim_start().len();
+            text.insert(sign_at, '-');
+        }
+        text
+    }
+}
+
+// A TextLabel that shows a number, for scores, timers, lives and so on
+pub struct NumberDisplay {
+    label: TextLabel,
+    format: NumberFormat,
+    anchor: [f32; 2],
+    value: i64,
+}
+
+impl NumberDisplay {
+    pub fn new(
+        sprites: &mut SpriteRender,
+        gpu: &WGPU,
+        font: &mut dyn Font,
+        layout: TextLayout,
+        format: NumberFormat,
+        camera: GPUCamera,
+    ) -> Self {
+        let text = format.format(0);
+        let anchor = layout.position;
+        let layout = aligned(font, gpu, layout, anchor, format.align, &text);
+        let label = TextLabel::new(sprites, gpu, font, layout, &text, camera);
+        Self {
+            label,
+            format,
+            anchor,
+            value: 0,
+        }
+    }
+
+    pub fn group(&self) -> SpriteGroupId {
+        self.label.group()
+    }
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    pub fn set_value(
+        &mut self,
+        sprites: &mut SpriteRender,
+        gpu: &WGPU,
+        font: &mut dyn Font,
+        value: i64,
+    ) -> Result<(), SpriteError> {
+        if value == self.value {
+            return Ok(());
+        }
+        let text = self.format.format(value);
+        // Right aligned numbers move over when they change length
+        let layout = aligned(
+            font,
+            gpu,
+            self.label.layout(),
+            self.anchor,
+            self.format.align,
+            &text,
+        );
+        self.label.layout = layout;
+        self.label.set_text(sprites, gpu, font, &text)?;
+        self.value = value;
+        Ok(())
+    }
+}
+
+// Where the label for `text` has to start so that it lines up with `anchor`
+fn aligned(
+    font: &mut dyn Font,
+    gpu: &WGPU,
+    layout: TextLayout,
+    anchor: [f32; 2],
+    align: Align,
+    text: &str,
+) -> TextLayout {
+    let x = match align {
+        Align::Left => anchor[0],
+        Align::Right => anchor[0] - measure_text(font, gpu, &layout, text),
+    };
+    TextLayout {
+        position: [x, anchor[1]],
+        ..layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+
+    #[test]
+    fn separators_go_between_groups_of_three() {
+        let commas = NumberFormat {
+            separator: Some(','),
+            ..Default::default()
+        };
+        assert_eq!(commas.format(1234567), "1,234,567");
+        assert_eq!(commas.format(123456), "123,456");
+        assert_eq!(commas.format(999), "999");
+        assert_eq!(commas.format(-1234), "-1,234");
+        assert_eq!(commas.format(0), "0");
+    }
+
+    #[test]
+    fn short_numbers_are_padded() {
+        let blanks = NumberFormat {
+            min_digits: 3,
+            ..Default::default()
+        };
+        let zeros = NumberFormat {
+            leading_zeros: true,
+            ..blanks
+        };
+        assert_eq!(blanks.format(5), "  5");
+        assert_eq!(zeros.format(5), "005");
+        // Numbers that are already long enough aren't cut off
+        assert_eq!(zeros.format(12345), "12345");
+        // The sign doesn't count as a digit, and sits after blank padding but before zeros
+        assert_eq!(blanks.format(-5), "  -5");
+        assert_eq!(zeros.format(-5), "-005");
+        assert_eq!(blanks.format(i64::MIN), "-9223372036854775808");
+    }
+
+    #[test]
+    fn separators_skip_blank_padding() {
+        let blanks = NumberFormat {
+            min_digits: 5,
+            separator: Some(','),
+            ..Default::default()
+        };
+        let zeros = NumberFormat {
+            leading_zeros: true,
+            ..blanks
+        };
+        assert_eq!(blanks.format(42), "   42");
+        assert_eq!(zeros.format(42), "00,042");
+        assert_eq!(blanks.format(-1042), " -1,042");
+    }
+
+    // Every character one unit wide, like a monospace font at glyph_size 1
+    fn lines(text: &str, wrap_width: Option<f32>) -> Vec<&str> {
+        let chars: Vec<_> = text.chars().map(|c| (c, None, 1.0)).collect();
+        let layout = TextLayout {
+            position: [0.0, 0.0],
+            glyph_size: [1.0, 1.0],
+            spacing: 0.0,
+            wrap_width,
+        };
+        layout_lines(&chars, &layout)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect()
+    }
+
+    #[test]
+    fn newlines_always_break() {
+        assert_eq!(lines("ab\ncd", None), ["ab", "cd"]);
+        assert_eq!(lines("ab\n\ncd\n", Some(10.0)), ["ab", "", "cd", ""]);
+    }
+
+    #[test]
+    fn long_lines_break_between_words() {
+        assert_eq!(lines("aa bb cc", None), ["aa bb cc"]);
+        // "aa bb" fits in 5 exactly
+        assert_eq!(lines("aa bb cc", Some(5.0)), ["aa bb ", "cc"]);
+        // Starting a new line resets the width
+        assert_eq!(lines("aaaa\nb cc", Some(4.0)), ["aaaa", "b cc"]);
+    }
+
+    #[test]
+    fn spaces_stay_at_the_end_of_the_line_they_follow() {
+        // So character i is still sprite i, and the next line starts on a letter
+        assert_eq!(lines("aa   bb", Some(3.0)), ["aa   ", "bb"]);
+        assert_eq!(lines("aaa bbb ", Some(3.0)), ["aaa ", "bbb "]);
+    }
+
+    #[test]
+    fn words_longer_than_a_line_get_a_line_of_their_own() {
+        assert_eq!(
+            lines("a verylongword b", Some(4.0)),
+            ["a ", "verylongword ", "b"]
+        );
+        // Without an empty line in front when it's the first word
+        assert_eq!(lines("verylongword", Some(4.0)), ["verylongword"]);
+    }
+
+    // A headless WGPU and a 10 by 1 grid font of the digits. Returns None (and the test passes
+    // trivially) if this machine has no adapter at all.
+    fn digit_font() -> Option<(WGPU, BitmapFont)> {
+        let gpu = test_gpu()?;
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 80,
+                height: 8,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let font = BitmapFont::grid(texture, [8, 8], "0123456789");
+        Some((gpu, font))
+    }
+
+    #[test]
+    fn right_aligned_numbers_keep_their_right_edge() {
+        let Some((gpu, mut font)) = digit_font() else {
+            return;
+        };
+        let mut sprites = SpriteRender::new(&gpu);
+        let layout = TextLayout {
+            position: [100.0, 10.0],
+            glyph_size: [8.0, 8.0],
+            spacing: 2.0,
+            wrap_width: None,
+        };
+        let format = NumberFormat {
+            align: Align::Right,
+            ..Default::default()
+        };
+        let camera = GPUCamera {
+            screen_pos: [0.0, 0.0],
+            screen_size: [100.0, 100.0],
+        };
+        let mut display = NumberDisplay::new(&mut sprites, &gpu, &mut font, layout, format, camera);
+        for value in [0, 12345, 7, 100] {
+            display
+                .set_value(&mut sprites, &gpu, &mut font, value)
+                .unwrap();
+            let layout = display.label.layout();
+            let width = measure_text(&mut font, &gpu, &layout, &value.to_string());
+            assert_eq!(layout.position, [100.0 - width, 10.0], "{}", value);
+        }
+    }
+
+    // The digit font, but with 1 and 1 pulled a quarter of an em together
+    struct Kerned(BitmapFont);
+
+    impl Font for Kerned {
+        fn texture(&self) -> &wgpu::Texture {
+            self.0.texture()
+        }
+        fn glyph(&mut self, gpu: &WGPU, c: char) -> Option<Glyph> {
+            self.0.glyph(gpu, c)
+        }
+        fn kerning(&self, left: char, right: char) -> f32 {
+            if (left, right) == ('1', '1') {
+                -0.25
+            } else {
+                0.0
+            }
+        }
+    }
+
+    #[test]
+    fn kerning_moves_the_next_glyph() {
+        let Some((gpu, font)) = digit_font() else {
+            return;
+        };
+        let mut font = Kerned(font);
+        let layout = TextLayout {
+            position: [0.0, 0.0],
+            glyph_size: [8.0, 8.0],
+            spacing: 0.0,
+            wrap_width: None,
+        };
+        assert_eq!(measure_text(&mut font, &gpu, &layout, "12"), 16.0);
+        assert_eq!(measure_text(&mut font, &gpu, &layout, "11"), 14.0);
+        assert_eq!(measure_text(&mut font, &gpu, &layout, "111"), 20.0);
+    }
+}
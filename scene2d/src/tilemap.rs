@@ -0,0 +1,1182 @@
+use crate::{Engine, GPUCamera, GPUSprite, SpriteGroupId};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Tiled keeps a tile's flips in the top bits of its global id
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const GID_MASK: u32 = !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY);
+
+#[derive(Debug)]
+pub enum TileMapError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Xml(xml::reader::Error),
+    Image(image::ImageError),
+    // Something the file asks for that we don't handle, like compressed layer data
+    Unsupported(String),
+    // A required attribute is missing or unreadable
+    Invalid(String),
+}
+
+impl std::fmt::Display for TileMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TileMapError::Io(err) => write!(f, "couldn't read map: {}", err),
+            TileMapError::Json(err) => write!(f, "bad .tmj map: {}", err),
+            TileMapError::Xml(err) => write!(f, "bad .tmx map: {}", err),
+            TileMapError::Image(err) => write!(f, "couldn't load tileset image: {}", err),
+            TileMapError::Unsupported(what) => write!(f, "unsupported in maps: {}", what),
+            TileMapError::Invalid(what) => write!(f, "bad map: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for TileMapError {}
+
+impl From<std::io::Error> for TileMapError {
+    fn from(err: std::io::Error) -> Self {
+        TileMapError::Io(err)
+    }
+}
+impl From<serde_json::Error> for TileMapError {
+    fn from(err: serde_json::Error) -> Self {
+        TileMapError::Json(err)
+    }
+}
+impl From<xml::reader::Error> for TileMapError {
+    fn from(err: xml::reader::Error) -> Self {
+        TileMapError::Xml(err)
+    }
+}
+impl From<image::ImageError> for TileMapError {
+    fn from(err: image::ImageError) -> Self {
+        TileMapError::Image(err)
+    }
+}
+
+// A Tiled custom property. Colors and files come through as strings, object references as ints.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Property {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Property::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Property::Int(i) => Some(*i as f32),
+            Property::Float(f) => Some(*f as f32),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Property::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn parse(kind: &str, value: &str) -> Option<Self> {
+        Some(match kind {
+            "bool" => Property::Bool(value.parse().ok()?),
+            "int" | "object" => Property::Int(value.parse().ok()?),
+            "float" => Property::Float(value.parse().ok()?),
+            _ => Property::String(value.to_string()),
+        })
+    }
+}
+
+pub type Properties = HashMap<String, Property>;
+
+pub struct Tileset {
+    // The global id of this tileset's first tile
+    pub first_gid: u32,
+    pub name: String,
+    pub image: PathBuf,
+    pub image_size: [u32; 2],
+    pub tile_size: [u32; 2],
+    pub columns: u32,
+    pub tile_count: u32,
+    // Pixels around the edge of the image and between tiles
+    pub margin: u32,
+    pub spacing: u32,
+}
+
+impl Tileset {
+    // Where tile `id` (counting from 0 within this tileset) is in its image, as a sheet_region
+    pub fn sheet_region(&self, id: u32) -> [f32; 4] {
+        let columns = self.columns.max(1);
+        let [tw, th] = self.tile_size;
+        let x = self.margin + (id % columns) * (tw + self.spacing);
+        let y = self.margin + (id / columns) * (th + self.spacing);
+        let [w, h] = [self.image_size[0] as f32, self.image_size[1] as f32];
+        [x as f32 / w, y as f32 / h, tw as f32 / w, th as f32 / h]
+    }
+}
+
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    // Row by row from the top left. 0 is an empty cell; the rest are global ids, flip bits and all.
+    pub tiles: Vec<u32>,
+    pub offset: [f32; 2],
+    pub opacity: f32,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Rectangle,
+    Ellipse,
+    Point,
+    // Points relative to the object's position
+    Polygon(Vec<[f32; 2]>),
+    Polyline(Vec<[f32; 2]>),
+}
+
+// Positions and sizes here are as Tiled has them: map pixels, y pointing down. TileMap::world_rect
+// converts them.
+#[derive(Clone, Debug)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    // Tiled's "class" (called "type" in older versions)
+    pub class: String,
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    // Degrees clockwise around `position`
+    pub rotation: f32,
+    pub shape: Shape,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub properties: Properties,
+}
+
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+// A level made in the Tiled map editor (https://www.mapeditor.org), saved as .tmj (JSON) or
+// .tmx (XML). Only orthogonal maps with CSV (or, in .tmx, unencoded) tile data are supported.
+// Group layers are flattened; image layers are skipped.
+//
+// Tiled puts y = 0 at the top and we put it at the bottom, so everything handed out in world
+// coordinates is flipped, then multiplied by `scale` and moved by `origin`.
+pub struct TileMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: [u32; 2],
+    pub tilesets: Vec<Tileset>,
+    pub layers: Vec<Layer>,
+    pub properties: Properties,
+    // World units per map pixel
+    pub scale: f32,
+    // Where the bottom left corner of the map goes in the world
+    pub origin: [f32; 2],
+}
+
+impl TileMap {
+    // Reads a .tmx or .tmj (going by the extension). Tileset images and external tilesets are
+    // looked up relative to the map.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TileMapError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        if path.extension().is_some_and(|ext| ext == "tmx") {
+            Self::parse_tmx(&text, dir)
+        } else {
+            Self::parse_tmj(&text, dir)
+        }
+    }
+
+    pub fn parse_tmj(json: &str, dir: &Path) -> Result<Self, TileMapError> {
+        let map: tmj::Map = serde_json::from_str(json)?;
+        tmj::convert(map, dir)
+    }
+
+    pub fn parse_tmx(xml: &str, dir: &Path) -> Result<Self, TileMapError> {
+        tmx::convert(&tmx::Element::parse(xml)?, dir)
+    }
+
+    // Size of the whole map in map pixels
+    pub fn pixel_size(&self) -> [f32; 2] {
+        [
+            (self.width * self.tile_size[0]) as f32,
+            (self.height * self.tile_size[1]) as f32,
+        ]
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Tiles(tiles) => Some(tiles),
+            Layer::Objects(_) => None,
+        })
+    }
+    pub fn object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Objects(objects) => Some(objects),
+            Layer::Tiles(_) => None,
+        })
+    }
+    pub fn tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.tile_layers().find(|layer| layer.name == name)
+    }
+    pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.object_layers().find(|layer| layer.name == name)
+    }
+    // Every object on every object layer
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.object_layers().flat_map(|layer| layer.objects.iter())
+    }
+
+    // An object's bounds in the world, [x, y, width, height] from its bottom left like
+    // GPUSprite::screen_region. Rotated objects get the smallest box that holds all of them.
+    pub fn world_rect(&self, object: &MapObject) -> [f32; 4] {
+        let [x, y] = object.position;
+        let [w, h] = object.size;
+        let (sin, cos) = object.rotation.to_radians().sin_cos();
+        let corners = [[0.0, 0.0], [w, 0.0], [0.0, h], [w, h]]
+            .map(|[dx, dy]| [x + dx * cos - dy * sin, y + dx * sin + dy * cos]);
+        let [left, top] = corners
+            .into_iter()
+            .reduce(|a, b| [a[0].min(b[0]), a[1].min(b[1])])
+            .expect("four corners");
+        let [right, bottom] = corners
+            .into_iter()
+            .reduce(|a, b| [a[0].max(b[0]), a[1].max(b[1])])
+            .expect("four corners");
+        let map_height = self.pixel_size()[1];
+        [
+            self.origin[0] + left * self.scale,
+            self.origin[1] + (map_height - bottom) * self.scale,
+            (right - left) * self.scale,
+            (bottom - top) * self.scale,
+        ]
+    }
+
+    // Visible rectangle objects as solid geometry, rotated ones as the box around them. Objects
+    // with the class "spawn" are left out.
+    pub fn colliders(&self) -> Vec<[f32; 4]> {
+        self.objects()
+            .filter(|object| {
+                object.visible && object.shape == Shape::Rectangle && object.class != "spawn"
+            })
+            .map(|object| self.world_rect(object))
+            .collect()
+    }
+
+    // Where the point object (or object with the class "spawn") called `name` is in the world
+    pub fn spawn_point(&self, name: &str) -> Option<[f32; 2]> {
+        self.objects()
+            .find(|object| {
+                object.name == name && (object.shape == Shape::Point || object.class == "spawn")
+            })
+            .map(|object| {
+                let [x, y, _, _] = self.world_rect(object);
+                [x, y]
+            })
+    }
+
+    // Adds sprite groups drawing every visible tile layer, bottom layer first. A layer gets one
+    // group per tileset it uses, since a group can only draw from one texture.
+    pub fn add_tile_layers(
+        &self,
+        engine: &mut Engine,
+        camera: GPUCamera,
+    ) -> Result<Vec<SpriteGroupId>, TileMapError> {
+        let mut groups = Vec::new();
+        for layer in self.tile_layers().filter(|layer| layer.visible) {
+            groups.extend(self.add_layer(engine, layer, camera)?);
+        }
+        Ok(groups)
+    }
+
+    pub fn add_layer(
+        &self,
+        engine: &mut Engine,
+        layer: &TileLayer,
+        camera: GPUCamera,
+    ) -> Result<Vec<SpriteGroupId>, TileMapError> {
+        let mut groups = Vec::new();
+        for (tileset, sprites) in self.tilesets.iter().zip(self.layer_sprites(layer)) {
+            if sprites.is_empty() {
+                continue;
+            }
+            let texture = engine.texture(&tileset.image)?;
+            groups.push(
+                engine
+                    .sprites
+                    .add_sprite_group(&engine.gpu, &texture, sprites, camera),
+            );
+        }
+        Ok(groups)
+    }
+
+    // A sprite for every tile in the layer, sorted by tileset in the same order as `tilesets`
+    pub fn layer_sprites(&self, layer: &TileLayer) -> Vec<Vec<GPUSprite>> {
+        let [cw, ch] = [self.tile_size[0] as f32, self.tile_size[1] as f32];
+        let map_height = self.pixel_size()[1];
+        let mut by_tileset: Vec<Vec<GPUSprite>> = self.tilesets.iter().map(|_| vec![]).collect();
+        for (cell, &gid) in layer.tiles.iter().enumerate() {
+            let Some((which, id)) = self.tileset_for(gid & GID_MASK) else {
+                continue;
+            };
+            let tileset = &self.tilesets[which];
+            let [tw, th] = [tileset.tile_size[0] as f32, tileset.tile_size[1] as f32];
+            let col = (cell as u32 % layer.width) as f32;
+            let row = (cell as u32 / layer.width) as f32;
+            // Tiles bigger than a cell stick up and to the right from its bottom left corner
+            let mut x = col * cw + layer.offset[0];
+            let mut y = map_height - (row + 1.0) * ch - layer.offset[1];
+            let flip_h = gid & FLIPPED_HORIZONTALLY != 0;
+            let flip_v = gid & FLIPPED_VERTICALLY != 0;
+            let (rotation, flip_x, flip_y) = if gid & FLIPPED_DIAGONALLY != 0 {
+                // Tiled's diagonal flip swaps x and y, which is a quarter turn counter-clockwise
+                // of the tile flipped horizontally. Its other flips happen after that, so they
+                // swap places when moved to before the turn.
+                // The turned tile is th wide and tw tall; it still has to start at the corner.
+                x += (th - tw) / 2.0;
+                y += (tw - th) / 2.0;
+                (std::f32::consts::FRAC_PI_2, !flip_v, flip_h)
+            } else {
+                (0.0, flip_h, flip_v)
+            };
+            let mut sprite = GPUSprite::new(
+                [
+                    self.origin[0] + x * self.scale,
+                    self.origin[1] + y * self.scale,
+                    tw * self.scale,
+                    th * self.scale,
+                ],
+                tileset.sheet_region(id),
+            );
+            sprite.rotation = rotation;
+            sprite.set_flag(GPUSprite::FLIP_X, flip_x);
+            sprite.set_flag(GPUSprite::FLIP_Y, flip_y);
+            sprite.color[3] = layer.opacity;
+            by_tileset[which].push(sprite);
+        }
+        by_tileset
+    }
+
+    // Which tileset a global id (without flip bits) is from, and its id within that tileset
+    fn tileset_for(&self, gid: u32) -> Option<(usize, u32)> {
+        if gid == 0 {
+            return None;
+        }
+        // Tilesets are in first_gid order, so it's the last one starting at or before gid
+        let which = self
+            .tilesets
+            .iter()
+            .rposition(|tileset| tileset.first_gid <= gid)?;
+        Some((which, gid - self.tilesets[which].first_gid))
+    }
+}
+
+// Every cell needs exactly one gid, or tiles end up in the wrong rows
+fn check_size(layer: &TileLayer) -> Result<(), TileMapError> {
+    let cells = layer.width as u64 * layer.height as u64;
+    if cells != layer.tiles.len() as u64 {
+        return Err(TileMapError::Invalid(format!(
+            "layer {:?} is {}x{} but has {} tiles",
+            layer.name,
+            layer.width,
+            layer.height,
+            layer.tiles.len()
+        )));
+    }
+    Ok(())
+}
+
+// Tile data is either a plain list of gids (.tmj) or comma separated (.tmx)
+fn check_encoding(encoding: Option<&str>, compression: Option<&str>) -> Result<(), TileMapError> {
+    if let Some(compression) = compression.filter(|c| !c.is_empty()) {
+        return Err(TileMapError::Unsupported(format!(
+            "{} compressed tile data, save the map with CSV layer format",
+            compression
+        )));
+    }
+    match encoding {
+        None | Some("csv") => Ok(()),
+        Some(other) => Err(TileMapError::Unsupported(format!(
+            "{} tile data, save the map with CSV layer format",
+            other
+        ))),
+    }
+}
+
+// The JSON (.tmj/.tsj) format
+mod tmj {
+    use super::*;
+
+    fn yes() -> bool {
+        true
+    }
+    fn one() -> f32 {
+        1.0
+    }
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct Map {
+        width: u32,
+        height: u32,
+        tilewidth: u32,
+        tileheight: u32,
+        #[serde(default)]
+        orientation: Option<String>,
+        #[serde(default)]
+        infinite: bool,
+        tilesets: Vec<TilesetRef>,
+        layers: Vec<JsonLayer>,
+        #[serde(default)]
+        properties: Vec<JsonProperty>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct TilesetRef {
+        firstgid: u32,
+        // Set for external tilesets, which keep everything else in their own file
+        #[serde(default)]
+        source: Option<PathBuf>,
+        #[serde(flatten)]
+        tileset: JsonTileset,
+    }
+
+    #[derive(serde::Deserialize, Default)]
+    #[serde(default)]
+    struct JsonTileset {
+        name: String,
+        image: Option<PathBuf>,
+        imagewidth: u32,
+        imageheight: u32,
+        tilewidth: u32,
+        tileheight: u32,
+        columns: u32,
+        tilecount: u32,
+        margin: u32,
+        spacing: u32,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    enum JsonLayer {
+        Tilelayer {
+            name: String,
+            width: u32,
+            height: u32,
+            data: serde_json::Value,
+            #[serde(default)]
+            encoding: Option<String>,
+            #[serde(default)]
+            compression: Option<String>,
+            #[serde(default)]
+            offsetx: f32,
+            #[serde(default)]
+            offsety: f32,
+            #[serde(default = "one")]
+            opacity: f32,
+            #[serde(default = "yes")]
+            visible: bool,
+            #[serde(default)]
+            properties: Vec<JsonProperty>,
+        },
+        Objectgroup {
+            name: String,
+            objects: Vec<JsonObject>,
+            #[serde(default)]
+            properties: Vec<JsonProperty>,
+        },
+        Group {
+            layers: Vec<JsonLayer>,
+        },
+        #[serde(other)]
+        Other,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct JsonObject {
+        id: u32,
+        #[serde(default)]
+        name: String,
+        #[serde(default, alias = "class")]
+        r#type: String,
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        width: f32,
+        #[serde(default)]
+        height: f32,
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default)]
+        point: bool,
+        #[serde(default)]
+        ellipse: bool,
+        #[serde(default)]
+        polygon: Option<Vec<Point>>,
+        #[serde(default)]
+        polyline: Option<Vec<Point>>,
+        #[serde(default = "yes")]
+        visible: bool,
+        #[serde(default)]
+        properties: Vec<JsonProperty>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Point {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct JsonProperty {
+        name: String,
+        #[serde(default)]
+        r#type: String,
+        value: serde_json::Value,
+    }
+
+    fn properties(list: Vec<JsonProperty>) -> Properties {
+        list.into_iter()
+            .filter_map(|prop| {
+                let value = match prop.value {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                Some((prop.name, Property::parse(&prop.r#type, &value)?))
+            })
+            .collect()
+    }
+
+    fn tileset(first_gid: u32, json: JsonTileset, dir: &Path) -> Result<Tileset, TileMapError> {
+        let image = json.image.ok_or_else(|| {
+            TileMapError::Unsupported(format!(
+                "tileset {:?} is a collection of images, not one image",
+                json.name
+            ))
+        })?;
+        Ok(Tileset {
+            first_gid,
+            name: json.name,
+            image: dir.join(image),
+            image_size: [json.imagewidth, json.imageheight],
+            tile_size: [json.tilewidth, json.tileheight],
+            columns: json.columns,
+            tile_count: json.tilecount,
+            margin: json.margin,
+            spacing: json.spacing,
+        })
+    }
+
+    fn layers(list: Vec<JsonLayer>, out: &mut Vec<Layer>) -> Result<(), TileMapError> {
+        for layer in list {
+            match layer {
+                JsonLayer::Tilelayer {
+                    name,
+                    width,
+                    height,
+                    data,
+                    encoding,
+                    compression,
+                    offsetx,
+                    offsety,
+                    opacity,
+                    visible,
+                    properties: props,
+                } => {
+                    check_encoding(encoding.as_deref(), compression.as_deref())?;
+                    let tiles: Vec<u32> = serde_json::from_value(data)?;
+                    let layer = TileLayer {
+                        name,
+                        width,
+                        height,
+                        tiles,
+                        offset: [offsetx, offsety],
+                        opacity,
+                        visible,
+                        properties: properties(props),
+                    };
+                    check_size(&layer)?;
+                    out.push(Layer::Tiles(layer));
+                }
+                JsonLayer::Objectgroup {
+                    name,
+                    objects,
+                    properties: props,
+                } => out.push(Layer::Objects(ObjectLayer {
+                    name,
+                    objects: objects.into_iter().map(object).collect(),
+                    properties: properties(props),
+                })),
+                JsonLayer::Group { layers: children } => layers(children, out)?,
+                JsonLayer::Other => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn object(json: JsonObject) -> MapObject {
+        let points = |list: Vec<Point>| list.into_iter().map(|p| [p.x, p.y]).collect();
+        let shape = if json.point {
+            Shape::Point
+        } else if json.ellipse {
+            Shape::Ellipse
+        } else if let Some(polygon) = json.polygon {
+            Shape::Polygon(points(polygon))
+        } else if let Some(polyline) = json.polyline {
+            Shape::Polyline(points(polyline))
+        } else {
+            Shape::Rectangle
+        };
+        MapObject {
+            id: json.id,
+            name: json.name,
+            class: json.r#type,
+            position: [json.x, json.y],
+            size: [json.width, json.height],
+            rotation: json.rotation,
+            shape,
+            visible: json.visible,
+            properties: properties(json.properties),
+        }
+    }
+
+    pub(super) fn convert(map: Map, dir: &Path) -> Result<TileMap, TileMapError> {
+        if map
+            .orientation
+            .as_deref()
+            .is_some_and(|o| o != "orthogonal")
+        {
+            return Err(TileMapError::Unsupported(format!(
+                "{} maps",
+                map.orientation.unwrap_or_default()
+            )));
+        }
+        if map.infinite {
+            return Err(TileMapError::Unsupported("infinite maps".to_string()));
+        }
+        let mut tilesets = Vec::new();
+        for tileset_ref in map.tilesets {
+            tilesets.push(match tileset_ref.source {
+                Some(source) => external_tileset(tileset_ref.firstgid, &dir.join(source))?,
+                None => tileset(tileset_ref.firstgid, tileset_ref.tileset, dir)?,
+            });
+        }
+        let mut out = Vec::new();
+        layers(map.layers, &mut out)?;
+        Ok(TileMap {
+            width: map.width,
+            height: map.height,
+            tile_size: [map.tilewidth, map.tileheight],
+            tilesets,
+            layers: out,
+            properties: properties(map.properties),
+            scale: 1.0,
+            origin: [0.0, 0.0],
+        })
+    }
+
+    // A .tsj or .tsx next to the map
+    pub(super) fn external_tileset(first_gid: u32, path: &Path) -> Result<Tileset, TileMapError> {
+        let text = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        if path.extension().is_some_and(|ext| ext == "tsx") {
+            return tmx::tileset(first_gid, &tmx::Element::parse(&text)?, dir);
+        }
+        tileset(first_gid, serde_json::from_str(&text)?, dir)
+    }
+}
+
+// The XML (.tmx/.tsx) format
+mod tmx {
+    use super::*;
+    use xml::reader::{EventReader, XmlEvent};
+
+    // Just enough of a DOM to walk a map file
+    pub(super) struct Element {
+        name: String,
+        attributes: HashMap<String, String>,
+        children: Vec<Element>,
+        text: String,
+    }
+
+    impl Element {
+        pub(super) fn parse(xml: &str) -> Result<Self, TileMapError> {
+            let mut stack: Vec<Element> = Vec::new();
+            for event in EventReader::new(xml.as_bytes()) {
+                match event? {
+                    XmlEvent::StartElement {
+                        name, attributes, ..
+                    } => stack.push(Element {
+                        name: name.local_name,
+                        attributes: attributes
+                            .into_iter()
+                            .map(|attr| (attr.name.local_name, attr.value))
+                            .collect(),
+                        children: Vec::new(),
+                        text: String::new(),
+                    }),
+                    XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                        if let Some(element) = stack.last_mut() {
+                            element.text.push_str(&text);
+                        }
+                    }
+                    XmlEvent::EndElement { .. } => {
+                        let element = stack.pop().expect("the parser checks nesting");
+                        match stack.last_mut() {
+                            Some(parent) => parent.children.push(element),
+                            None => return Ok(element),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Err(TileMapError::Invalid("empty document".to_string()))
+        }
+
+        fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+            self.children.iter().filter(move |child| child.name == name)
+        }
+        fn child(&self, name: &str) -> Option<&Element> {
+            self.children.iter().find(|child| child.name == name)
+        }
+        fn attr(&self, name: &str) -> Option<&str> {
+            self.attributes.get(name).map(String::as_str)
+        }
+        // A required attribute parsed as T
+        fn get<T: std::str::FromStr>(&self, name: &str) -> Result<T, TileMapError> {
+            self.attr(name)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| {
+                    TileMapError::Invalid(format!("<{}> needs a valid {}", self.name, name))
+                })
+        }
+        // An optional attribute parsed as T
+        fn get_or<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, TileMapError> {
+            match self.attr(name) {
+                None => Ok(default),
+                Some(_) => self.get(name),
+            }
+        }
+    }
+
+    fn properties(element: &Element) -> Properties {
+        let Some(props) = element.child("properties") else {
+            return Properties::new();
+        };
+        props
+            .children("property")
+            .filter_map(|prop| {
+                let name = prop.attr("name")?.to_string();
+                // Multi-line strings go in the body instead of the value attribute
+                let value = prop.attr("value").unwrap_or(&prop.text);
+                Some((
+                    name,
+                    Property::parse(prop.attr("type").unwrap_or("string"), value)?,
+                ))
+            })
+            .collect()
+    }
+
+    pub(super) fn tileset(
+        first_gid: u32,
+        element: &Element,
+        dir: &Path,
+    ) -> Result<Tileset, TileMapError> {
+        let name = element.attr("name").unwrap_or_default().to_string();
+        let image = element.child("image").ok_or_else(|| {
+            TileMapError::Unsupported(format!(
+                "tileset {:?} is a collection of images, not one image",
+                name
+            ))
+        })?;
+        Ok(Tileset {
+            first_gid,
+            name,
+            image: dir.join(image.get::<String>("source")?),
+            image_size: [image.get("width")?, image.get("height")?],
+            tile_size: [element.get("tilewidth")?, element.get("tileheight")?],
+            columns: element.get("columns")?,
+            tile_count: element.get("tilecount")?,
+            margin: element.get_or("margin", 0)?,
+            spacing: element.get_or("spacing", 0)?,
+        })
+    }
+
+    fn tile_layer(element: &Element) -> Result<TileLayer, TileMapError> {
+        let data = element
+            .child("data")
+            .ok_or_else(|| TileMapError::Invalid("<layer> without <data>".to_string()))?;
+        check_encoding(data.attr("encoding"), data.attr("compression"))?;
+        let tiles = if data.attr("encoding").is_some() {
+            data.text
+                .split(',')
+                .map(|gid| gid.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| TileMapError::Invalid("bad CSV tile data".to_string()))?
+        } else {
+            // Unencoded: one <tile gid=".."/> per cell
+            data.children("tile")
+                .map(|tile| tile.get_or("gid", 0))
+                .collect::<Result<_, _>>()?
+        };
+        let layer = TileLayer {
+            name: element.attr("name").unwrap_or_default().to_string(),
+            width: element.get("width")?,
+            height: element.get("height")?,
+            tiles,
+            offset: [
+                element.get_or("offsetx", 0.0)?,
+                element.get_or("offsety", 0.0)?,
+            ],
+            opacity: element.get_or("opacity", 1.0)?,
+            visible: element.get_or("visible", 1)? != 0,
+            properties: properties(element),
+        };
+        check_size(&layer)?;
+        Ok(layer)
+    }
+
+    fn points(element: &Element) -> Result<Vec<[f32; 2]>, TileMapError> {
+        element
+            .attr("points")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|pair| {
+                let (x, y) = pair.split_once(',')?;
+                Some([x.parse().ok()?, y.parse().ok()?])
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| TileMapError::Invalid("bad polygon points".to_string()))
+    }
+
+    fn object(element: &Element) -> Result<MapObject, TileMapError> {
+        let shape = if element.child("point").is_some() {
+            Shape::Point
+        } else if element.child("ellipse").is_some() {
+            Shape::Ellipse
+        } else if let Some(polygon) = element.child("polygon") {
+            Shape::Polygon(points(polygon)?)
+        } else if let Some(polyline) = element.child("polyline") {
+            Shape::Polyline(points(polyline)?)
+        } else {
+            Shape::Rectangle
+        };
+        let class = element.attr("type").or(element.attr("class"));
+        Ok(MapObject {
+            id: element.get("id")?,
+            name: element.attr("name").unwrap_or_default().to_string(),
+            class: class.unwrap_or_default().to_string(),
+            position: [element.get("x")?, element.get("y")?],
+            size: [
+                element.get_or("width", 0.0)?,
+                element.get_or("height", 0.0)?,
+            ],
+            rotation: element.get_or("rotation", 0.0)?,
+            shape,
+            visible: element.get_or("visible", 1)? != 0,
+            properties: properties(element),
+        })
+    }
+
+    // Layers in file order, with group layers flattened
+    fn layers(element: &Element, out: &mut Vec<Layer>) -> Result<(), TileMapError> {
+        for child in &element.children {
+            match child.name.as_str() {
+                "layer" => out.push(Layer::Tiles(tile_layer(child)?)),
+                "objectgroup" => out.push(Layer::Objects(ObjectLayer {
+                    name: child.attr("name").unwrap_or_default().to_string(),
+                    objects: child
+                        .children("object")
+                        .map(object)
+                        .collect::<Result<_, _>>()?,
+                    properties: properties(child),
+                })),
+                "group" => layers(child, out)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn convert(map: &Element, dir: &Path) -> Result<TileMap, TileMapError> {
+        if map.name != "map" {
+            return Err(TileMapError::Invalid("not a <map>".to_string()));
+        }
+        let orientation = map.attr("orientation").unwrap_or("orthogonal");
+        if orientation != "orthogonal" {
+            return Err(TileMapError::Unsupported(format!("{} maps", orientation)));
+        }
+        if map.get_or("infinite", 0)? != 0 {
+            return Err(TileMapError::Unsupported("infinite maps".to_string()));
+        }
+        let mut tilesets = Vec::new();
+        for element in map.children("tileset") {
+            let first_gid = element.get("firstgid")?;
+            tilesets.push(match element.attr("source") {
+                Some(source) => tmj::external_tileset(first_gid, &dir.join(source))?,
+                None => tileset(first_gid, element, dir)?,
+            });
+        }
+        let mut out = Vec::new();
+        layers(map, &mut out)?;
+        Ok(TileMap {
+            width: map.get("width")?,
+            height: map.get("height")?,
+            tile_size: [map.get("tilewidth")?, map.get("tileheight")?],
+            tilesets,
+            layers: out,
+            properties: properties(map),
+            scale: 1.0,
+            origin: [0.0, 0.0],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same little map saved both ways: 4x3 cells of 16 pixels, an embedded tileset of 16x16
+    // tiles and an external one of 32x16 tiles
+    fn maps() -> [TileMap; 2] {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/testdata/");
+        ["map.tmj", "map.tmx"].map(|file| {
+            TileMap::load(format!("{}{}", dir, file))
+                .unwrap_or_else(|err| panic!("{}: {}", file, err))
+        })
+    }
+
+    fn error(result: Result<TileMap, TileMapError>) -> TileMapError {
+        match result {
+            Ok(_) => panic!("parsed a bad map"),
+            Err(err) => err,
+        }
+    }
+
+    fn close(a: [f32; 4], b: [f32; 4]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4)
+    }
+
+    const H: u32 = FLIPPED_HORIZONTALLY;
+    const V: u32 = FLIPPED_VERTICALLY;
+    const D: u32 = FLIPPED_DIAGONALLY;
+
+    #[test]
+    fn tile_layers_keep_gids_and_flip_bits() {
+        for map in maps() {
+            assert_eq!(map.pixel_size(), [64.0, 48.0]);
+            let names: Vec<_> = map.tile_layers().map(|layer| layer.name.as_str()).collect();
+            // The group is flattened and the image layer skipped
+            assert_eq!(names, ["ground", "front"]);
+            assert_eq!(map.layers.len(), 3);
+
+            let ground = map.tile_layer("ground").unwrap();
+            assert_eq!((ground.width, ground.height), (4, 3));
+            assert_eq!(
+                ground.tiles,
+                [0, 0, 0, 9, 0, 1, 2 | H, 0, 3 | D | H, 4 | V, 0, 10 | D]
+            );
+            assert!(ground.visible);
+            assert_eq!(ground.properties["solid"], Property::Bool(true));
+
+            // Unencoded <tile> elements in the .tmx
+            let front = map.tile_layer("front").unwrap();
+            assert_eq!(front.tiles, [0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0]);
+            assert_eq!(front.offset, [4.0, -2.0]);
+            assert_eq!(front.opacity, 0.5);
+            assert!(!front.visible);
+        }
+    }
+
+    #[test]
+    fn properties_keep_their_types() {
+        for map in maps() {
+            let props = &map.properties;
+            assert_eq!(props["dark"].as_bool(), Some(true));
+            assert_eq!(props["gravity"].as_f32(), Some(9.5));
+            assert_eq!(props["lives"], Property::Int(3));
+            assert_eq!(props["title"].as_str(), Some("Test level"));
+            let things = map.object_layer("things").unwrap();
+            assert_eq!(things.properties["music"].as_str(), Some("song.ogg"));
+        }
+    }
+
+    #[test]
+    fn objects_keep_their_shapes() {
+        for map in maps() {
+            let objects: Vec<_> = map.objects().collect();
+            let names: Vec<_> = objects.iter().map(|o| o.name.as_str()).collect();
+            assert_eq!(
+                names,
+                ["wall", "ramp", "secret", "start", "coin", "pond", "fence", "zone"]
+            );
+            let [wall, ramp, secret, start, coin, pond, fence, zone] = objects[..] else {
+                unreachable!()
+            };
+            assert_eq!((wall.id, wall.class.as_str()), (1, "solid"));
+            assert_eq!((wall.position, wall.size), ([16.0, 0.0], [32.0, 16.0]));
+            assert_eq!(wall.properties["bounce"].as_f32(), Some(0.25));
+            // "class" is the newer name for "type"
+            assert_eq!(ramp.class, "solid");
+            assert_eq!(ramp.rotation, 90.0);
+            assert!(!secret.visible);
+            assert_eq!(start.shape, Shape::Point);
+            assert_eq!(coin.class, "spawn");
+            assert_eq!(pond.shape, Shape::Ellipse);
+            assert_eq!(
+                fence.shape,
+                Shape::Polyline(vec![[0.0, 0.0], [16.0, 0.0], [16.0, -8.0]])
+            );
+            assert_eq!(
+                zone.shape,
+                Shape::Polygon(vec![[0.0, 0.0], [8.0, 0.0], [0.0, 8.0]])
+            );
+        }
+    }
+
+    #[test]
+    fn external_tilesets_are_read_from_their_own_file() {
+        for map in maps() {
+            let [walker, wide] = &map.tilesets[..] else {
+                panic!("two tilesets")
+            };
+            assert_eq!((walker.first_gid, walker.name.as_str()), (1, "walker"));
+            assert_eq!(walker.tile_size, [16, 16]);
+            assert_eq!((wide.first_gid, wide.name.as_str()), (9, "wide"));
+            assert_eq!(wide.tile_size, [32, 16]);
+            assert_eq!((wide.columns, wide.tile_count), (2, 4));
+            assert_eq!(wide.image_size, [64, 32]);
+            // Images are found relative to whichever file names them
+            assert!(wide.image.ends_with("testdata/walker.png"));
+            assert!(wide.image.exists());
+            assert_eq!(wide.sheet_region(3), [0.5, 0.5, 0.5, 0.5]);
+        }
+    }
+
+    #[test]
+    fn objects_land_in_world_coordinates() {
+        for mut map in maps() {
+            map.scale = 2.0;
+            map.origin = [100.0, 50.0];
+            let wall = map.objects().find(|o| o.name == "wall").unwrap();
+            // y flips: the wall's top is the map's top, 48 pixels up
+            assert_eq!(map.world_rect(wall), [132.0, 114.0, 64.0, 32.0]);
+
+            // The ramp turns a quarter clockwise around its top left corner, so it ends up
+            // 8 wide and 16 tall, left of and below that corner
+            let colliders = map.colliders();
+            assert_eq!(colliders.len(), 2, "{:?}", colliders);
+            assert_eq!(colliders[0], [132.0, 114.0, 64.0, 32.0]);
+            assert!(
+                close(colliders[1], [148.0, 50.0, 16.0, 32.0]),
+                "{:?}",
+                colliders
+            );
+
+            assert_eq!(map.spawn_point("start"), Some([116.0, 66.0]));
+            assert_eq!(map.spawn_point("coin"), Some([196.0, 82.0]));
+            assert_eq!(map.spawn_point("wall"), None);
+        }
+    }
+
+    #[test]
+    fn tiles_become_sprites() {
+        for map in maps() {
+            let ground = map.layer_sprites(map.tile_layer("ground").unwrap());
+            let [walker, wide] = &ground[..] else {
+                panic!("one list per tileset")
+            };
+            let regions: Vec<_> = walker.iter().map(|s| s.screen_region).collect();
+            assert_eq!(
+                regions,
+                [
+                    [16.0, 16.0, 16.0, 16.0],
+                    [32.0, 16.0, 16.0, 16.0],
+                    [0.0, 0.0, 16.0, 16.0],
+                    [16.0, 0.0, 16.0, 16.0],
+                ]
+            );
+            assert_eq!(walker[0].sheet_region, [0.0, 0.0, 0.25, 0.5]);
+            assert_eq!(walker[0].flags, 0);
+            assert_eq!(walker[1].flags, GPUSprite::FLIP_X);
+            assert_eq!(walker[3].flags, GPUSprite::FLIP_Y);
+            // Diagonal and horizontal is Tiled's "rotate right": a quarter turn
+            // counter-clockwise, then upside down
+            assert_eq!(walker[2].rotation, std::f32::consts::FRAC_PI_2);
+            assert_eq!(walker[2].flags, GPUSprite::FLIP_X | GPUSprite::FLIP_Y);
+
+            // Wide tiles stick out to the right of their cell
+            assert_eq!(wide[0].screen_region, [48.0, 32.0, 32.0, 16.0]);
+            assert_eq!(wide[0].sheet_region, [0.0, 0.0, 0.5, 0.5]);
+            // and once turned, up out of it, still from its bottom left corner
+            assert_eq!(wide[1].screen_region, [40.0, 8.0, 32.0, 16.0]);
+            assert_eq!(wide[1].flags, GPUSprite::FLIP_X);
+
+            let front = map.layer_sprites(map.tile_layer("front").unwrap());
+            assert_eq!(front[0].len(), 1);
+            assert_eq!(front[0][0].screen_region, [20.0, 18.0, 16.0, 16.0]);
+            assert_eq!(front[0][0].sheet_region, [0.0, 0.5, 0.25, 0.5]);
+            assert_eq!(front[0][0].color[3], 0.5);
+        }
+    }
+
+    #[test]
+    fn layers_need_one_gid_per_cell() {
+        let json = |width, height, data| {
+            format!(
+                r#"{{ "width": 2, "height": 2, "tilewidth": 8, "tileheight": 8, "tilesets": [],
+                    "layers": [{{ "type": "tilelayer", "name": "bad", "width": {}, "height": {},
+                                  "data": {} }}] }}"#,
+                width, height, data
+            )
+        };
+        assert!(TileMap::parse_tmj(&json(2, 2, "[0, 0, 0, 0]"), Path::new("")).is_ok());
+        for (width, height, data) in [(0, 2, "[1, 1]"), (2, 2, "[1, 1, 1]"), (2, 1, "[1, 1, 1]")] {
+            let err = error(TileMap::parse_tmj(
+                &json(width, height, data),
+                Path::new(""),
+            ));
+            assert!(matches!(err, TileMapError::Invalid(_)), "{}", err);
+        }
+
+        let xml = r#"<map width="2" height="2" tilewidth="8" tileheight="8">
+            <layer name="bad" width="2" height="2"><data encoding="csv">1,1,1</data></layer>
+        </map>"#;
+        let err = error(TileMap::parse_tmx(xml, Path::new("")));
+        assert!(matches!(err, TileMapError::Invalid(_)), "{}", err);
+    }
+
+    #[test]
+    fn compressed_and_infinite_maps_are_unsupported() {
+        let xml = r#"<map width="1" height="1" tilewidth="8" tileheight="8">
+            <layer name="zipped" width="1" height="1">
+                <data encoding="base64" compression="zlib">eJxjYGAAAAAEAAE=</data>
+            </layer>
+        </map>"#;
+        let err = error(TileMap::parse_tmx(xml, Path::new("")));
+        assert!(matches!(err, TileMapError::Unsupported(_)), "{}", err);
+        let json = r#"{ "width": 1, "height": 1, "tilewidth": 8, "tileheight": 8,
+                        "infinite": true, "tilesets": [], "layers": [] }"#;
+        let err = error(TileMap::parse_tmj(json, Path::new("")));
+        assert!(matches!(err, TileMapError::Unsupported(_)), "{}", err);
+    }
+}
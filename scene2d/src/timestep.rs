@@ -0,0 +1,106 @@
+// Longest frame that gets simulated in full. After a hitch (a breakpoint, dragging the window)
+// the game slows down instead of running hundreds of ticks to catch up.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+// Turns however long each rendered frame took into a whole number of fixed-length simulation
+// ticks, carrying the leftover time over to the next frame
+pub(crate) struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub(crate) fn new(tick_rate: f32) -> Self {
+        Self {
+            step: step_for(tick_rate),
+            accumulator: 0.0,
+        }
+    }
+
+    // Seconds per tick
+    pub(crate) fn step(&self) -> f32 {
+        self.step
+    }
+    pub(crate) fn set_tick_rate(&mut self, tick_rate: f32) {
+        self.step = step_for(tick_rate);
+    }
+
+    // Adds a frame's worth of time
+    pub(crate) fn advance(&mut self, frame_time: f32) {
+        self.accumulator += frame_time.min(MAX_FRAME_TIME);
+    }
+    // Takes one tick's worth of time out if there's enough for one
+    pub(crate) fn tick(&mut self) -> bool {
+        if self.accumulator < self.step {
+            return false;
+        }
+        self.accumulator -= self.step;
+        true
+    }
+    // How far between the last tick and the next one this frame is, from 0 to 1
+    pub(crate) fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}
+
+// A rate of 0 would never tick again, and a negative or infinite one would tick forever
+fn step_for(tick_rate: f32) -> f32 {
+    assert!(
+        tick_rate.is_finite() && tick_rate > 0.0,
+        "tick rate has to be a positive number of ticks a second, not {}",
+        tick_rate
+    );
+    1.0 / tick_rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(timestep: &mut FixedTimestep, frame_time: f32) -> usize {
+        timestep.advance(frame_time);
+        std::iter::from_fn(|| timestep.tick().then_some(())).count()
+    }
+
+    #[test]
+    fn tick_count_follows_time_not_frames() {
+        // One second at 60Hz and at 144Hz is 60 ticks either way
+        for fps in [60.0, 144.0] {
+            let mut timestep = FixedTimestep::new(60.0);
+            let total: usize = (0..fps as usize)
+                .map(|_| ticks(&mut timestep, 1.0 / fps))
+                .sum();
+            assert!((59..=60).contains(&total), "{} ticks at {}fps", total, fps);
+        }
+    }
+
+    #[test]
+    fn leftover_time_becomes_alpha() {
+        let mut timestep = FixedTimestep::new(10.0);
+        assert_eq!(ticks(&mut timestep, 0.25), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut timestep = FixedTimestep::new(60.0);
+        assert_eq!(ticks(&mut timestep, 10.0), 15);
+    }
+
+    #[test]
+    fn tick_rates_can_change() {
+        let mut timestep = FixedTimestep::new(60.0);
+        timestep.set_tick_rate(10.0);
+        assert_eq!(timestep.step(), 0.1);
+        assert_eq!(ticks(&mut timestep, 0.25), 2);
+    }
+
+    #[test]
+    fn tick_rates_have_to_be_positive_and_finite() {
+        for rate in [0.0, -60.0, f32::NAN, f32::INFINITY] {
+            let changed = std::panic::catch_unwind(|| FixedTimestep::new(60.0).set_tick_rate(rate));
+            assert!(changed.is_err(), "{} was allowed", rate);
+            assert!(std::panic::catch_unwind(|| FixedTimestep::new(rate)).is_err());
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::atlas::ShelfPacker;
+use crate::{BlendMode, Font, Glyph, WGPU};
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont};
+use std::collections::HashMap;
+
+// Width and height of the texture glyphs get rasterized into
+const ATLAS_SIZE: u32 = 512;
+// Empty pixels around each glyph so neighbours don't bleed in when sampling
+const PADDING: u32 = 1;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    // The file isn't a TrueType/OpenType font ab_glyph can read
+    Invalid,
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io(err) => write!(f, "couldn't read font: {}", err),
+            FontError::Invalid => write!(f, "not a valid TrueType/OpenType font"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<std::io::Error> for FontError {
+    fn from(err: std::io::Error) -> Self {
+        FontError::Io(err)
+    }
+}
+
+// A .ttf/.otf font rasterized at one pixel size. Glyphs are rasterized the first time they're
+// used and packed into a texture, so it only holds the characters actually shown.
+// Draw text with it at `glyph_size: [px_size, px_size]` for pixel-perfect glyphs. Kerning
+// comes from the font's legacy `kern` table only; pairs kerned through GPOS aren't adjusted.
+pub struct TtfFont {
+    font: FontVec,
+    px_size: f32,
+    texture: wgpu::Texture,
+    glyphs: HashMap<char, Option<Glyph>>,
+    packer: ShelfPacker,
+    warned_full: bool,
+}
+
+impl TtfFont {
+    pub fn load(
+        gpu: &WGPU,
+        path: impl AsRef<std::path::Path>,
+        px_size: f32,
+    ) -> Result<Self, FontError> {
+        let data = std::fs::read(path)?;
+        let font = FontVec::try_from_vec(data).map_err(|_| FontError::Invalid)?;
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        Ok(Self {
+            font,
+            px_size,
+            texture,
+            glyphs: HashMap::new(),
+            packer: ShelfPacker::new([ATLAS_SIZE, ATLAS_SIZE], PADDING),
+            warned_full: false,
+        })
+    }
+
+    pub fn px_size(&self) -> f32 {
+        self.px_size
+    }
+
+    fn rasterize(&mut self, gpu: &WGPU, c: char) -> Option<Glyph> {
+        let scaled = self.font.as_scaled(PxScale::from(self.px_size));
+        let id = scaled.glyph_id(c);
+        if id.0 == 0 {
+            return None;
+        }
+        let advance = scaled.h_advance(id) / self.px_size;
+        // The pen sits on the bottom of the line, so the baseline is `descent` above it
+        let baseline = -scaled.descent();
+        let Some(outline) = self.font.outline_glyph(scaled.scaled_glyph(c)) else {
+            // Spaces and the like
+            return Some(Glyph {
+                sheet_region: [0.0; 4],
+                offset: [0.0, 0.0],
+                size: [0.0, 0.0],
+                advance,
+            });
+        };
+        let bounds = outline.px_bounds();
+        let (w, h) = (bounds.width() as u32, bounds.height() as u32);
+        let Some([x, y]) = self.packer.allocate(w, h) else {
+            if !self.warned_full {
+                log::warn!("glyph atlas is full, can't rasterize {:?}", c);
+                self.warned_full = true;
+            }
+            return None;
+        };
+        // White, with coverage as alpha, so tinting the sprite colors the text
+        let mut pixels = vec![0u8; (w * h * 4) as usize];
+        outline.draw(|px, py, coverage| {
+            let i = ((py * w + px) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&[255, 255, 255, (coverage * 255.0) as u8]);
+        });
+        if w > 0 && h > 0 {
+            gpu.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * w),
+                    rows_per_image: Some(h),
+                },
+                wgpu::Extent3d {
+                    width: w,
+                    height: h,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        let atlas = ATLAS_SIZE as f32;
+        Some(Glyph {
+            sheet_region: [
+                x as f32 / atlas,
+                y as f32 / atlas,
+                w as f32 / atlas,
+                h as f32 / atlas,
+            ],
+            // px_bounds has y pointing down from the baseline; ours points up from the pen
+            offset: [
+                bounds.min.x / self.px_size,
+                (baseline - bounds.max.y) / self.px_size,
+            ],
+            size: [w as f32 / self.px_size, h as f32 / self.px_size],
+            advance,
+        })
+    }
+}
+
+impl Font for TtfFont {
+    fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    fn glyph(&mut self, gpu: &WGPU, c: char) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return *glyph;
+        }
+        let glyph = self.rasterize(gpu, c);
+        // Characters the font doesn't have are remembered, but ones that didn't fit in the
+        // atlas get another try next time
+        if glyph.is_some() || self.font.glyph_id(c).0 == 0 {
+            self.glyphs.insert(c, glyph);
+        }
+        glyph
+    }
+    fn kerning(&self, left: char, right: char) -> f32 {
+        let scaled = self.font.as_scaled(PxScale::from(self.px_size));
+        scaled.kern(scaled.glyph_id(left), scaled.glyph_id(right)) / self.px_size
+    }
+    fn line_height(&self) -> f32 {
+        let scaled = self.font.as_scaled(PxScale::from(self.px_size));
+        (scaled.height() + scaled.line_gap()) / self.px_size
+    }
+    // Antialiased edges need real blending rather than cutout
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::PremultipliedAlpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_gpu;
+
+    // Cantarell, under the OFL (see testdata/Cantarell-OFL.txt)
+    const FONT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/testdata/Cantarell-Regular.ttf"
+    );
+
+    // Returns None (and the test passes trivially) if this machine has no adapter at all
+    fn setup() -> Option<(WGPU, TtfFont)> {
+        let gpu = test_gpu()?;
+        let font = TtfFont::load(&gpu, FONT, 32.0).unwrap();
+        Some((gpu, font))
+    }
+
+    #[test]
+    fn glyphs_sit_on_the_baseline() {
+        let Some((gpu, mut font)) = setup() else {
+            return;
+        };
+        let a = font.glyph(&gpu, 'A').unwrap();
+        let g = font.glyph(&gpu, 'g').unwrap();
+        let space = font.glyph(&gpu, ' ').unwrap();
+        assert!(a.advance > 0.3 && a.advance < 1.0, "{:?}", a);
+        assert!(a.size[0] > 0.3 && a.size[1] > 0.5, "{:?}", a);
+        // The pen is on the bottom of the line, so letters start a descent's height above it
+        // and descenders reach down below that
+        assert!(a.offset[1] > 0.1, "{:?}", a);
+        assert!(g.offset[1] < a.offset[1], "{:?} {:?}", g, a);
+        assert_eq!(space.size, [0.0, 0.0]);
+        assert!(space.advance > 0.0);
+        assert!(font.line_height() >= 1.0);
+    }
+
+    #[test]
+    fn missing_characters_have_no_glyph() {
+        let Some((gpu, mut font)) = setup() else {
+            return;
+        };
+        assert_eq!(font.glyph(&gpu, '中'), None);
+    }
+
+    #[test]
+    fn glyphs_are_packed_once_without_overlapping() {
+        let Some((gpu, mut font)) = setup() else {
+            return;
+        };
+        let text = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+        let regions: Vec<[f32; 4]> = text
+            .chars()
+            .map(|c| font.glyph(&gpu, c).unwrap().sheet_region)
+            .collect();
+        for (i, a) in regions.iter().enumerate() {
+            assert!(a[0] >= 0.0 && a[1] >= 0.0 && a[0] + a[2] <= 1.0 && a[1] + a[3] <= 1.0);
+            for b in &regions[i + 1..] {
+                let apart = a[0] + a[2] <= b[0]
+                    || b[0] + b[2] <= a[0]
+                    || a[1] + a[3] <= b[1]
+                    || b[1] + b[3] <= a[1];
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        // Asking again reuses the rasterized glyph rather than packing it a second time
+        assert_eq!(font.glyph(&gpu, 'A').unwrap().sheet_region, regions[0]);
+    }
+
+    #[test]
+    fn glyphs_that_dont_fit_are_tried_again() {
+        let Some((gpu, _)) = setup() else {
+            return;
+        };
+        // Big enough that the alphabet fills the atlas
+        let mut font = TtfFont::load(&gpu, FONT, 200.0).unwrap();
+        let missed = ('A'..='Z')
+            .find(|&c| font.glyph(&gpu, c).is_none())
+            .expect("the atlas never filled up");
+        assert!(!font.glyphs.contains_key(&missed));
+        assert!(font.glyph(&gpu, '中').is_none());
+        assert_eq!(font.glyphs.get(&'中'), Some(&None));
+
+        // Once there's room again it shows up
+        font.packer = ShelfPacker::new([ATLAS_SIZE, ATLAS_SIZE], PADDING);
+        assert!(font.glyph(&gpu, missed).is_some());
+    }
+
+    #[test]
+    fn fonts_without_a_kern_table_dont_kern() {
+        let Some((_gpu, font)) = setup() else {
+            return;
+        };
+        // Cantarell only kerns through GPOS
+        assert_eq!(font.kerning('A', 'V'), 0.0);
+    }
+
+    // The smallest font ab_glyph will load: no outlines, just 'A' and 'V', 1000 units tall,
+    // with a kern table pulling "AV" together by 100 units
+    fn kerned_font() -> Vec<u8> {
+        fn be(values: &[i32], size: usize) -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|v| v.to_be_bytes()[4 - size..].to_vec())
+                .collect()
+        }
+        let u16s = |values: &[i32]| be(values, 2);
+        let head = [
+            be(&[0x10000, 0x10000, 0, 0x5F0F3CF5], 4),
+            // flags, units per em, created and modified, bounds, style, ppem, direction,
+            // short loca offsets, glyph format
+            u16s(&[0, 1000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 2, 0, 0]),
+        ]
+        .concat();
+        let hhea = [
+            be(&[0x10000], 4),
+            // ascender, descender, line gap, then nothing useful until the metric count
+            u16s(&[800, -200, 0, 600, 0, 0, 600, 1, 0, 0, 0, 0, 0, 0, 0, 3]),
+        ]
+        .concat();
+        let maxp = [be(&[0x5000], 4), u16s(&[3])].concat();
+        let hmtx = u16s(&[600, 0, 600, 0, 600, 0]);
+        // A format 6 cmap covering 'A' to 'V'
+        let mut ids = vec![0; 22];
+        ids[0] = 1;
+        ids[21] = 2;
+        let cmap = [
+            u16s(&[0, 1, 3, 1]),
+            be(&[12], 4),
+            u16s(&[6, 10 + 2 * 22, 0, 'A' as i32, 22]),
+            u16s(&ids),
+        ]
+        .concat();
+        // One horizontal format 0 subtable with one pair
+        let kern = u16s(&[0, 1, 0, 20, 1, 1, 6, 0, 0, 1, 2, -100]);
+
+        let tables = [
+            (b"cmap", cmap),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"kern", kern),
+            (b"maxp", maxp),
+        ];
+        let mut offset = 12 + 16 * tables.len();
+        let mut font = [be(&[0x10000], 4), u16s(&[tables.len() as i32, 64, 2, 32])].concat();
+        let mut data = Vec::new();
+        for (tag, table) in &tables {
+            font.extend_from_slice(*tag);
+            font.extend(be(&[0, offset as i32, table.len() as i32], 4));
+            data.extend_from_slice(table);
+            data.resize(data.len().next_multiple_of(4), 0);
+            offset = 12 + 16 * tables.len() + data.len();
+        }
+        font.extend(data);
+        font
+    }
+
+    #[test]
+    fn kern_tables_move_pairs_together() {
+        let Some(gpu) = test_gpu() else {
+            return;
+        };
+        let path = std::env::temp_dir().join(format!("kerned-test-{}.ttf", std::process::id()));
+        std::fs::write(&path, kerned_font()).unwrap();
+        let font = TtfFont::load(&gpu, &path, 32.0);
+        std::fs::remove_file(&path).unwrap();
+        let font = font.unwrap();
+        // In ems, so the same at any size
+        assert!((font.kerning('A', 'V') + 0.1).abs() < 1e-5);
+        assert_eq!(font.kerning('V', 'A'), 0.0);
+    }
+}